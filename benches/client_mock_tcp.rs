@@ -8,7 +8,7 @@ extern crate test;
 use std::fmt::{mod, Show};
 use std::str::from_str;
 use std::io::{IoResult, MemReader};
-use std::io::net::ip::{SocketAddr, ToSocketAddr};
+use std::io::net::ip::SocketAddr;
 use std::os;
 use std::path::BytesContainer;
 
@@ -92,8 +92,10 @@ impl net::NetworkStream for MockStream {
     }
 }
 
-impl net::NetworkConnector for MockStream {
-    fn connect<To: ToSocketAddr>(_addr: To, _scheme: &str) -> IoResult<MockStream> {
+struct MockConnector;
+
+impl net::NetworkConnector<MockStream> for MockConnector {
+    fn connect(&self, _host: &str, _port: hyper::Port, _scheme: &str) -> hyper::HttpResult<MockStream> {
         Ok(MockStream::new())
     }
 
@@ -103,8 +105,8 @@ impl net::NetworkConnector for MockStream {
 fn bench_mock_hyper(b: &mut test::Bencher) {
     let url = "http://127.0.0.1:1337/";
     b.iter(|| {
-        let mut req = hyper::client::Request::with_stream::<MockStream>(
-            hyper::Get, hyper::Url::parse(url).unwrap()).unwrap();
+        let mut req = hyper::client::Request::with_connector(
+            hyper::Get, hyper::Url::parse(url).unwrap(), &MockConnector).unwrap();
         req.headers_mut().set(Foo);
 
         req
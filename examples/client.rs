@@ -5,7 +5,7 @@ use std::io::stdout;
 use std::io::util::copy;
 
 use hyper::Url;
-use hyper::client::Request;
+use hyper::client::Client;
 
 fn main() {
     let args = os::args();
@@ -26,15 +26,13 @@ fn main() {
     };
 
 
-    let req = match Request::get(url) {
-        Ok(req) => req,
+    let client = Client::new();
+
+    let mut res = match client.get(url).send() {
+        Ok(res) => res,
         Err(err) => panic!("Failed to connect: {}", err)
     };
 
-    let mut res = req
-        .start().unwrap() // failure: Error writing Headers
-        .send().unwrap(); // failure: Error reading Response head.
-
     println!("Response: {}", res.status);
     println!("{}", res.headers);
     match copy(&mut res, &mut stdout()) {
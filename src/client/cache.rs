@@ -0,0 +1,778 @@
+//! Client Caching
+//!
+//! A `Cache` keeps the responses a `Client` receives, as a private cache
+//! following [RFC 7234](https://tools.ietf.org/html/rfc7234), and answers
+//! later requests with them:
+//!
+//! ```no_run
+//! # use hyper::Url;
+//! # use hyper::client::{Client, Cache};
+//! let mut client = Client::new();
+//! client.set_cache(Some(Cache::on_disk(Path::new("/tmp/hyper-cache")).unwrap()));
+//! let url = Url::parse("http://example.domain/logo.png").unwrap();
+//! let first = client.get(url.clone()).send().unwrap();
+//! // answered from the cache while the first response is fresh
+//! let second = client.get(url).send().unwrap();
+//! ```
+//!
+//! Only responses to `GET` requests are stored, according to their
+//! `Cache-Control`, `Expires` and `Vary` headers. A fresh response is
+//! served without touching the network. A stale one is revalidated with
+//! `If-None-Match` and `If-Modified-Since`, and served again when the
+//! server answers `304 Not Modified`.
+//!
+//! A response that is stored is read whole before it is returned. Its body
+//! is kept as it was received, and decoded each time it is served.
+//! Requests with a `Range` or conditional header of their own are sent as
+//! they are. Responses that vary on `Cookie` or `Authorization` aren't
+//! stored, since those are only added once the request is started.
+use std::ascii::AsciiExt;
+use std::cmp::{max, min};
+use std::collections::HashMap;
+use std::io::{BufferedReader, File, IoResult, FileNotFound, USER_RWX};
+use std::io::fs::{mkdir_recursive, rename, unlink};
+use std::rand::random;
+use std::str::from_utf8;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use openssl::crypto::hash::{hash, HashType};
+use serialize::hex::ToHex;
+use time::{at_utc, get_time, Timespec};
+use url::Url;
+
+use client::Response;
+use client::redirect::{resolve_location, same_origin};
+use header::Headers;
+use header::common::{Age, CacheControl, ContentEncoding, ContentLength, Date, ETag, Expires,
+                     IfModifiedSince, IfNoneMatch, LastModified, Location, TransferEncoding,
+                     Vary};
+use header::common::cache_control::CacheDirective::{mod, NoCache, NoStore, MaxAge, MaxStale,
+                                                    MinFresh, MustRevalidate, OnlyIfCached,
+                                                    Public};
+use http::{read_status_line, RawStatus, LINE_ENDING};
+use method::Method::{mod, Get};
+use status::StatusClass::{Success, Redirection};
+use status::StatusCode::{NotModified, GatewayTimeout};
+use version::HttpVersion;
+use HttpResult;
+
+/// A response kept by a `Cache`.
+#[deriving(Clone)]
+pub struct Entry {
+    /// The HTTP version of the response.
+    pub version: HttpVersion,
+    /// The status code and reason of the response.
+    pub status: RawStatus,
+    /// The headers of the response, as updated by revalidations.
+    pub headers: Headers,
+    /// The body of the response, as it was received.
+    pub body: Vec<u8>,
+    /// The request header fields named by the `Vary` of the response, as
+    /// they were sent.
+    pub varied: Headers,
+    /// When the request was sent, in seconds since the epoch.
+    pub request_time: i64,
+    /// When the response was received, in seconds since the epoch.
+    pub response_time: i64,
+}
+
+// Heuristic freshness never lasts longer than a day.
+static MAX_HEURISTIC_LIFETIME: i64 = 24 * 60 * 60;
+
+impl Entry {
+    /// How long the response stays fresh after it was generated, in
+    /// seconds.
+    ///
+    /// Without a `max-age` or `Expires`, this is a tenth of the time since
+    /// its `Last-Modified`, for statuses that allow it.
+    pub fn freshness_lifetime(&self) -> i64 {
+        let max_age = directives(&self.headers).iter().filter_map(|d| match *d {
+            MaxAge(secs) => Some(secs as i64),
+            _ => None
+        }).next();
+        if let Some(max_age) = max_age {
+            return max_age;
+        }
+
+        let date = self.date();
+        if self.headers.has::<Expires>() {
+            // an invalid date means already expired
+            return match self.headers.get::<Expires>() {
+                Some(&Expires(tm)) => max(0, tm.to_timespec().sec - date),
+                None => 0
+            };
+        }
+        match self.headers.get::<LastModified>() {
+            Some(&LastModified(tm)) if cacheable_by_default(self.status.0) => {
+                min(max(0, (date - tm.to_timespec().sec) / 10), MAX_HEURISTIC_LIFETIME)
+            },
+            _ => 0
+        }
+    }
+
+    /// The age of the response at `now`, in seconds, as described in
+    /// [RFC 7234, section 4.2.3](https://tools.ietf.org/html/rfc7234#section-4.2.3).
+    pub fn current_age(&self, now: i64) -> i64 {
+        let apparent_age = max(0, self.response_time - self.date());
+        let age_value = self.headers.get::<Age>().map_or(0, |age| age.0.num_seconds());
+        let response_delay = self.response_time - self.request_time;
+        let corrected_initial_age = max(apparent_age, age_value + response_delay);
+        corrected_initial_age + (now - self.response_time)
+    }
+
+    // The `Date` of the response, or when it was received without one.
+    fn date(&self) -> i64 {
+        match self.headers.get::<Date>() {
+            Some(&Date(tm)) => tm.to_timespec().sec,
+            None => self.response_time
+        }
+    }
+
+    // Whether the request headers named by the `Vary` of the response are
+    // the same in `headers` as they were when it was stored.
+    fn matches(&self, headers: &Headers) -> bool {
+        match self.headers.get::<Vary>() {
+            Some(&Vary::Any) => false,
+            Some(&Vary::Fields(ref fields)) => fields.iter().all(|field| {
+                headers.get_raw(field[]) == self.varied.get_raw(field[])
+            }),
+            None => true
+        }
+    }
+
+    // The stored response, as it is served at `now`. A response that was
+    // just `validated` with the server is never stale.
+    fn response(&self, now: i64, validated: bool) -> HttpResult<Response> {
+        let age = self.current_age(now);
+        let mut headers = self.headers.clone();
+        headers.set(Age(Duration::seconds(age)));
+        if !validated && age >= self.freshness_lifetime() {
+            headers.set_raw("Warning", vec![b"110 - \"Response is Stale\"".to_vec()]);
+        }
+        Response::buffered(self.version, self.status.clone(), headers, self.body.clone(), None)
+    }
+}
+
+/// Where a `Cache` keeps its entries.
+///
+/// Entries are keyed by the Url of their request. A key holds a single
+/// entry, so responses that vary on request headers replace each other.
+pub trait Storage: Send + Sync {
+    /// The entry stored under `key`, if there is one.
+    fn get(&self, key: &str) -> Option<Entry>;
+
+    /// Store `entry` under `key`, replacing any entry already there.
+    fn put(&self, key: &str, entry: Entry) -> IoResult<()>;
+
+    /// Remove the entry stored under `key`, if there is one.
+    fn remove(&self, key: &str) -> IoResult<()>;
+}
+
+impl_for_box!(Storage {
+    fn get(&self, key: &str) -> Option<Entry>;
+    fn put(&self, key: &str, entry: Entry) -> IoResult<()>;
+    fn remove(&self, key: &str) -> IoResult<()>;
+})
+
+/// Storage that keeps entries in memory, for the life of the process.
+///
+/// Clones share the entries.
+#[deriving(Clone)]
+pub struct MemoryStorage {
+    entries: Arc<Mutex<HashMap<String, Entry>>>,
+}
+
+impl MemoryStorage {
+    /// An empty storage.
+    pub fn new() -> MemoryStorage {
+        MemoryStorage { entries: Arc::new(Mutex::new(HashMap::new())) }
+    }
+
+    /// The number of entries stored.
+    pub fn len(&self) -> uint {
+        self.entries.lock().len()
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn get(&self, key: &str) -> Option<Entry> {
+        self.entries.lock().get(&key.into_string()).map(|entry| entry.clone())
+    }
+
+    fn put(&self, key: &str, entry: Entry) -> IoResult<()> {
+        self.entries.lock().insert(key.into_string(), entry);
+        Ok(())
+    }
+
+    fn remove(&self, key: &str) -> IoResult<()> {
+        self.entries.lock().remove(&key.into_string());
+        Ok(())
+    }
+}
+
+/// Storage that keeps each entry in a file of a directory, so entries
+/// outlive the process.
+///
+/// Files are named after a hash of their key. An entry that can't be read
+/// back is treated as missing.
+#[deriving(Clone)]
+pub struct DiskStorage {
+    dir: Path,
+}
+
+impl DiskStorage {
+    /// Storage in `dir`, which is created if it doesn't exist.
+    pub fn new(dir: Path) -> IoResult<DiskStorage> {
+        if !dir.is_dir() {
+            try!(mkdir_recursive(&dir, USER_RWX));
+        }
+        Ok(DiskStorage { dir: dir })
+    }
+
+    /// The directory entries are stored in.
+    #[inline]
+    pub fn dir(&self) -> &Path { &self.dir }
+
+    fn path(&self, key: &str) -> Path {
+        self.dir.join(hash(HashType::SHA256, key.as_bytes()).to_hex())
+    }
+}
+
+impl Storage for DiskStorage {
+    fn get(&self, key: &str) -> Option<Entry> {
+        let file = match File::open(&self.path(key)) {
+            Ok(file) => file,
+            Err(_) => return None
+        };
+        match read_entry(&mut BufferedReader::new(file), key) {
+            Ok(entry) => entry,
+            Err(_e) => {
+                debug!("unreadable cache entry for {}: {}", key, _e);
+                None
+            }
+        }
+    }
+
+    fn put(&self, key: &str, entry: Entry) -> IoResult<()> {
+        // written aside first, so a reader never sees half an entry, under
+        // a name of its own, so that writers don't mix their entries
+        let path = self.path(key);
+        let tmp = path.with_extension(format!("{:08x}.tmp", random::<u32>()));
+        {
+            let mut file = try!(File::create(&tmp));
+            try!(write_entry(&mut file, key, &entry));
+        }
+        rename(&tmp, &path)
+    }
+
+    fn remove(&self, key: &str) -> IoResult<()> {
+        match unlink(&self.path(key)) {
+            Err(ref e) if e.kind == FileNotFound => Ok(()),
+            result => result
+        }
+    }
+}
+
+// An entry is stored as its key, its times, the varied request headers,
+// and then the response as it would be sent.
+fn write_entry(w: &mut Writer, key: &str, entry: &Entry) -> IoResult<()> {
+    try!(write!(w, "{}\r\n{} {}\r\n", key, entry.request_time, entry.response_time))
+    try!(write_headers(w, &entry.varied));
+    try!(w.write(LINE_ENDING));
+    try!(write!(w, "{} {} {}\r\n", entry.version, entry.status.0, entry.status.1))
+    try!(write_headers(w, &entry.headers));
+    try!(w.write(LINE_ENDING));
+    w.write(entry.body[])
+}
+
+// Each value goes on a line of its own, so fields sent several times, such
+// as `Set-Cookie`, are read back the same.
+fn write_headers(w: &mut Writer, headers: &Headers) -> IoResult<()> {
+    for header in headers.iter() {
+        for value in headers.get_raw(header.name()).unwrap().iter() {
+            try!(write!(w, "{}: ", header.name()))
+            try!(w.write(value[]));
+            try!(w.write(LINE_ENDING));
+        }
+    }
+    Ok(())
+}
+
+// `None` when the file holds the entry of another key.
+fn read_entry<R: Buffer>(r: &mut R, key: &str) -> HttpResult<Option<Entry>> {
+    let line = try!(r.read_line());
+    if line[].trim_right_chars(['\r', '\n'][]) != key {
+        return Ok(None);
+    }
+    let line = try!(r.read_line());
+    let times: Vec<i64> = line[].split(' ').filter_map(|t| from_str(t.trim())).collect();
+    if times.len() != 2 {
+        return Ok(None);
+    }
+    let varied = try!(Headers::from_raw(r));
+    let (version, status) = try!(read_status_line(r));
+    let headers = try!(Headers::from_raw(r));
+    let body = try!(r.read_to_end());
+    Ok(Some(Entry {
+        version: version,
+        status: status,
+        headers: headers,
+        body: body,
+        varied: varied,
+        request_time: times[0],
+        response_time: times[1],
+    }))
+}
+
+/// A private HTTP cache, for a `Client` to keep responses in.
+///
+/// Clones share the storage.
+#[deriving(Clone)]
+pub struct Cache {
+    storage: Arc<Box<Storage + Send + Sync>>,
+}
+
+impl Cache {
+    /// A cache keeping its entries in `storage`.
+    pub fn new<S: Storage>(storage: S) -> Cache {
+        Cache { storage: Arc::new(box storage as Box<Storage + Send + Sync>) }
+    }
+
+    /// A cache keeping its entries in memory.
+    pub fn in_memory() -> Cache {
+        Cache::new(MemoryStorage::new())
+    }
+
+    /// A cache keeping its entries in files in `dir`.
+    pub fn on_disk(dir: Path) -> IoResult<Cache> {
+        Ok(Cache::new(try!(DiskStorage::new(dir))))
+    }
+
+    /// The storage of this cache.
+    #[inline]
+    pub fn storage(&self) -> &Storage { &**self.storage }
+
+    /// Answer a request from the cache if it can be, or else call `send`
+    /// with the headers to send, possibly made conditional, and store the
+    /// response.
+    ///
+    /// A request with an unsafe method removes the stored responses of its
+    /// Url and `Location` once it succeeds.
+    pub fn send(&self, method: &Method, url: &Url, headers: Headers,
+                send: |Headers| -> HttpResult<Response>) -> HttpResult<Response> {
+        let key = cache_key(url);
+        if !method.safe() {
+            let res = try!(send(headers));
+            match res.status.class() {
+                Success | Redirection => self.invalidate(url, &res),
+                _ => ()
+            }
+            return Ok(res);
+        }
+        if *method != Get || bypasses(&headers) {
+            return send(headers);
+        }
+
+        let request_cc = directives(&headers);
+        let entry = self.storage.get(key[]).and_then(|entry| {
+            if entry.matches(&headers) { Some(entry) } else { None }
+        });
+        let mut entry = match entry {
+            Some(entry) => {
+                let now = get_time().sec;
+                if is_fresh_enough(&entry, &headers, now) {
+                    debug!("cache hit for {}", key);
+                    return entry.response(now, false);
+                }
+                Some(entry)
+            },
+            None => None
+        };
+        // nothing stored can answer without asking the server
+        if request_cc.contains(&OnlyIfCached) {
+            return Ok(Response::synthesized(GatewayTimeout, Headers::new(), vec![]));
+        }
+
+        let mut conditional = headers.clone();
+        let mut validated = false;
+        if let Some(ref entry) = entry {
+            if let Some(etag) = entry.headers.get::<ETag>() {
+                conditional.set(IfNoneMatch::EntityTags(vec![etag.0.clone()]));
+                validated = true;
+            }
+            if let Some(&LastModified(tm)) = entry.headers.get::<LastModified>() {
+                conditional.set(IfModifiedSince(tm));
+                validated = true;
+            }
+        }
+
+        let request_time = get_time().sec;
+        let res = try!(send(conditional));
+        let now = get_time().sec;
+
+        if validated && res.status == NotModified {
+            debug!("revalidated {}", key);
+            let mut entry = entry.take().unwrap();
+            freshen(&mut entry, &res.headers, request_time, now);
+            if let Err(_e) = self.storage.put(key[], entry.clone()) {
+                debug!("failed to store {}: {}", key, _e);
+            }
+            return entry.response(now, true);
+        }
+
+        if request_cc.contains(&NoStore) || !is_storable(&res) {
+            return Ok(res);
+        }
+        self.store(key[], &headers, request_time, now, res)
+    }
+
+    // Read the body of `res` and store it, returning a response with the
+    // body read from memory instead.
+    fn store(&self, key: &str, request_headers: &Headers, request_time: i64,
+             response_time: i64, mut res: Response) -> HttpResult<Response> {
+        let body = try!(res.read_to_end());
+        let trailers = res.trailers();
+
+        let mut headers = res.headers.clone();
+        // the body is kept whole, whatever framing it was received with
+        headers.remove::<TransferEncoding>();
+        headers.set(ContentLength(body.len()));
+
+        let mut varied = Headers::new();
+        if let Some(&Vary::Fields(ref fields)) = headers.get::<Vary>() {
+            for field in fields.iter() {
+                if let Some(value) = request_headers.get_raw(field[]) {
+                    varied.set_raw(field.clone(), value.to_vec());
+                }
+            }
+        }
+
+        let entry = Entry {
+            version: res.version,
+            status: res.status_raw().clone(),
+            headers: headers,
+            body: body,
+            varied: varied,
+            request_time: request_time,
+            response_time: response_time,
+        };
+        debug!("storing {}", key);
+        let stored = entry.clone();
+        if let Err(_e) = self.storage.put(key, stored) {
+            debug!("failed to store {}: {}", key, _e);
+        }
+        Response::buffered(entry.version, entry.status, entry.headers, entry.body, trailers)
+    }
+
+    // Remove what an unsafe request may have changed.
+    fn invalidate(&self, url: &Url, res: &Response) {
+        let mut urls = vec![url.clone()];
+        if let Some(location) = res.headers.get::<Location>() {
+            match resolve_location(url, location.0[]) {
+                Some(location) if same_origin(url, &location) => urls.push(location),
+                _ => ()
+            }
+        }
+        for url in urls.iter() {
+            let key = cache_key(url);
+            if let Err(_e) = self.storage.remove(key[]) {
+                debug!("failed to remove {}: {}", key, _e);
+            }
+        }
+    }
+}
+
+// The Url without its fragment, which is never sent.
+fn cache_key(url: &Url) -> String {
+    let mut url = url.clone();
+    url.fragment = None;
+    url.serialize()
+}
+
+fn directives(headers: &Headers) -> Vec<CacheDirective> {
+    headers.get::<CacheControl>().map_or(vec![], |cc| cc.0.clone())
+}
+
+// Requests that ask for something else than the whole current response.
+fn bypasses(headers: &Headers) -> bool {
+    ["Range", "If-Range", "If-Match", "If-None-Match", "If-Modified-Since",
+     "If-Unmodified-Since"].iter().any(|name| headers.get_raw(*name).is_some())
+}
+
+// Whether `entry` can answer a request with `headers` without asking the
+// server.
+fn is_fresh_enough(entry: &Entry, headers: &Headers, now: i64) -> bool {
+    let request_cc = directives(headers);
+    let response_cc = directives(&entry.headers);
+    let pragma_no_cache = !headers.has::<CacheControl>() &&
+        headers.get_raw("Pragma").map_or(false, |raw| {
+            raw.iter().any(|v| from_utf8(v[]).map_or(false, |v| v.contains("no-cache")))
+        });
+    if request_cc.contains(&NoCache) || response_cc.contains(&NoCache) || pragma_no_cache {
+        return false;
+    }
+
+    let age = entry.current_age(now);
+    let lifetime = entry.freshness_lifetime();
+    for directive in request_cc.iter() {
+        match *directive {
+            MaxAge(secs) if age > secs as i64 => return false,
+            MinFresh(secs) if lifetime - age < secs as i64 => return false,
+            _ => ()
+        }
+    }
+    if age < lifetime {
+        return true;
+    }
+    if response_cc.contains(&MustRevalidate) {
+        return false;
+    }
+    request_cc.iter().any(|directive| match *directive {
+        MaxStale(None) => true,
+        MaxStale(Some(secs)) => age - lifetime <= secs as i64,
+        _ => false
+    })
+}
+
+// Statuses that can be stored without explicit freshness, and be given a
+// heuristic one.
+fn cacheable_by_default(status: u16) -> bool {
+    match status {
+        200 | 203 | 204 | 300 | 301 | 404 | 405 | 410 | 414 | 501 => true,
+        _ => false
+    }
+}
+
+fn is_storable(res: &Response) -> bool {
+    let status = res.status_raw().0;
+    if status < 200 || status == 206 || status == 304 {
+        return false;
+    }
+    let cc = directives(&res.headers);
+    if cc.contains(&NoStore) {
+        return false;
+    }
+    match res.headers.get::<Vary>() {
+        Some(&Vary::Any) => return false,
+        Some(&Vary::Fields(ref fields)) if fields.iter().any(|field| is_unseen(field[])) => {
+            return false;
+        },
+        _ => ()
+    }
+    let explicit = res.headers.has::<Expires>() || cc.contains(&Public) ||
+        cc.iter().any(|directive| match *directive { MaxAge(_) => true, _ => false });
+    explicit || cacheable_by_default(status)
+}
+
+// Whether `field` is only added to a request once it's started, by a cookie
+// store, a signer, or a proxy, so that the cache can't match it.
+fn is_unseen(field: &str) -> bool {
+    ["Cookie", "Authorization", "Proxy-Authorization"].iter()
+        .any(|name| name.eq_ignore_ascii_case(field))
+}
+
+// Update a stored response with the headers of a `304 Not Modified`, to a
+// request sent at `request_time` and answered at `response_time`.
+fn freshen(entry: &mut Entry, headers: &Headers, request_time: i64, response_time: i64) {
+    for header in headers.iter() {
+        // these describe the body of the 304, if anything
+        if header.is::<ContentLength>() || header.is::<TransferEncoding>() ||
+                header.is::<ContentEncoding>() {
+            continue;
+        }
+        let value = headers.get_raw(header.name()).unwrap().to_vec();
+        entry.headers.set_raw(header.name().into_string(), value);
+    }
+    // the age starts over from the 304, not from the stored response
+    if !headers.has::<Date>() {
+        entry.headers.set(Date(at_utc(Timespec::new(response_time, 0))));
+    }
+    if !headers.has::<Age>() {
+        entry.headers.remove::<Age>();
+    }
+    entry.request_time = request_time;
+    entry.response_time = response_time;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Borrowed;
+    use std::io::{MemReader, TempDir};
+    use std::str::from_utf8;
+    use std::time::Duration;
+    use time::{at_utc, get_time, Timespec};
+
+    use url::Url;
+
+    use client::Response;
+    use header::Headers;
+    use header::common::{Age, Date, LastModified};
+    use http::RawStatus;
+    use method::Method::Get;
+    use status::StatusCode;
+    use version::HttpVersion::Http11;
+    use super::{Cache, Entry, Storage, MemoryStorage, DiskStorage, is_fresh_enough, read_entry,
+                write_entry};
+
+    fn entry(headers: &[(&str, &str)], received: i64) -> Entry {
+        let mut h = Headers::new();
+        for &(name, value) in headers.iter() {
+            h.set_raw(name.into_string(), vec![value.as_bytes().to_vec()]);
+        }
+        h.set(Date(at_utc(Timespec::new(received, 0))));
+        Entry {
+            version: Http11,
+            status: RawStatus(200, Borrowed("OK")),
+            headers: h,
+            body: b"cached".to_vec(),
+            varied: Headers::new(),
+            request_time: received,
+            response_time: received,
+        }
+    }
+
+    #[test]
+    fn test_max_age() {
+        let now = get_time().sec;
+        let entry = entry(&[("Cache-Control", "max-age=60")], now - 30);
+        assert_eq!(entry.freshness_lifetime(), 60);
+        assert_eq!(entry.current_age(now), 30);
+        assert!(is_fresh_enough(&entry, &Headers::new(), now));
+        assert!(!is_fresh_enough(&entry, &Headers::new(), now + 30));
+
+        let mut headers = Headers::new();
+        headers.set_raw("Cache-Control", vec![b"max-age=10".to_vec()]);
+        assert!(!is_fresh_enough(&entry, &headers, now));
+        headers.set_raw("Cache-Control", vec![b"max-stale=60".to_vec()]);
+        assert!(is_fresh_enough(&entry, &headers, now + 60));
+    }
+
+    #[test]
+    fn test_age_and_expires() {
+        let now = get_time().sec;
+        // an invalid Expires means already expired, rather than a heuristic
+        // freshness from the Last-Modified
+        let mut entry = entry(&[("Expires", "0")], now);
+        entry.headers.set(LastModified(at_utc(Timespec::new(now - 1000, 0))));
+        assert_eq!(entry.freshness_lifetime(), 0);
+        assert!(!is_fresh_enough(&entry, &Headers::new(), now));
+
+        entry.headers.set(Age(Duration::seconds(100)));
+        entry.headers.set_raw("Cache-Control", vec![b"max-age=60, must-revalidate".to_vec()]);
+        assert_eq!(entry.current_age(now), 100);
+        let mut headers = Headers::new();
+        headers.set_raw("Cache-Control", vec![b"max-stale".to_vec()]);
+        assert!(!is_fresh_enough(&entry, &headers, now));
+    }
+
+    #[test]
+    fn test_heuristic_freshness() {
+        let now = get_time().sec;
+        let mut entry = entry(&[], now);
+        entry.headers.set(LastModified(at_utc(Timespec::new(now - 1000, 0))));
+        assert_eq!(entry.freshness_lifetime(), 100);
+        entry.status = RawStatus(302, Borrowed("Found"));
+        assert_eq!(entry.freshness_lifetime(), 0);
+    }
+
+    #[test]
+    fn test_vary() {
+        let mut entry = entry(&[("Vary", "Accept-Language")], 0);
+        entry.varied.set_raw("Accept-Language", vec![b"fr".to_vec()]);
+        let mut headers = Headers::new();
+        assert!(!entry.matches(&headers));
+        headers.set_raw("accept-language", vec![b"fr".to_vec()]);
+        assert!(entry.matches(&headers));
+        entry.headers.set_raw("Vary", vec![b"*".to_vec()]);
+        assert!(!entry.matches(&headers));
+    }
+
+    #[test]
+    fn test_entry_round_trip() {
+        let mut entry = entry(&[("Set-Cookie", "a=1")], 1416000000);
+        entry.headers.set_raw("Set-Cookie", vec![b"a=1".to_vec(), b"b=2".to_vec()]);
+        entry.varied.set_raw("Accept-Language", vec![b"fr".to_vec()]);
+        let mut buf = vec![];
+        write_entry(&mut buf, "http://example.domain/", &entry).unwrap();
+
+        let read = read_entry(&mut MemReader::new(buf.clone()), "http://example.domain/")
+            .unwrap().unwrap();
+        assert_eq!(read.status, entry.status);
+        assert_eq!(read.body, entry.body);
+        assert_eq!(read.response_time, 1416000000);
+        assert_eq!(read.headers.get_raw("Set-Cookie").unwrap(),
+                   [b"a=1".to_vec(), b"b=2".to_vec()][]);
+        assert_eq!(read.varied.get_raw("accept-language").unwrap(), [b"fr".to_vec()][]);
+        assert_eq!(read.headers.get::<Date>(), entry.headers.get::<Date>());
+
+        let other = read_entry(&mut MemReader::new(buf), "http://other.domain/").unwrap();
+        assert!(other.is_none());
+    }
+
+    fn cache_with(entry: Entry) -> Cache {
+        let storage = MemoryStorage::new();
+        storage.put("http://example.domain/", entry).unwrap();
+        Cache::new(storage)
+    }
+
+    #[test]
+    fn test_only_if_cached_with_stale_entry() {
+        let now = get_time().sec;
+        let cache = cache_with(entry(&[("Cache-Control", "max-age=60")], now - 120));
+        let mut headers = Headers::new();
+        headers.set_raw("Cache-Control", vec![b"only-if-cached".to_vec()]);
+        let url = Url::parse("http://example.domain/").unwrap();
+        let res = cache.send(&Get, &url, headers, |_| panic!("sent to the server")).unwrap();
+        assert_eq!(res.status, StatusCode::GatewayTimeout);
+    }
+
+    #[test]
+    fn test_revalidated_not_stale() {
+        let now = get_time().sec;
+        let cache = cache_with(entry(&[("Cache-Control", "no-cache"), ("ETag", "\"v1\"")],
+                                     now - 120));
+        let url = Url::parse("http://example.domain/").unwrap();
+        let mut res = cache.send(&Get, &url, Headers::new(), |headers| {
+            assert!(headers.get_raw("If-None-Match").is_some());
+            Ok(Response::synthesized(StatusCode::NotModified, Headers::new(), vec![]))
+        }).unwrap();
+        assert_eq!(res.status, StatusCode::Ok);
+        // the age starts over, give or take the second the 304 took
+        assert!(res.headers.get::<Age>().unwrap().0 <= Duration::seconds(1));
+        assert!(res.headers.get_raw("Warning").is_none());
+        assert_eq!(res.read_to_string(), Ok("cached".into_string()));
+    }
+
+    #[test]
+    fn test_vary_on_unseen_header_not_stored() {
+        let cache = Cache::in_memory();
+        let url = Url::parse("http://example.domain/").unwrap();
+        let mut sent = 0u;
+        for _ in range(0u, 2) {
+            cache.send(&Get, &url, Headers::new(), |_| {
+                sent += 1;
+                let mut headers = Headers::new();
+                headers.set_raw("Cache-Control", vec![b"max-age=60".to_vec()]);
+                headers.set_raw("Vary", vec![b"Cookie".to_vec()]);
+                Ok(Response::synthesized(StatusCode::Ok, headers, b"private".to_vec()))
+            }).unwrap();
+        }
+        assert_eq!(sent, 2);
+    }
+
+    #[test]
+    fn test_disk_storage() {
+        // the directory is removed as it's dropped, even if the test fails
+        let dir = TempDir::new("hyper-cache-test").unwrap();
+        let storage = DiskStorage::new(dir.path().join("cache")).unwrap();
+        let key = "http://example.domain/";
+        assert!(storage.get(key).is_none());
+        storage.put(key, entry(&[], 0)).unwrap();
+        let entry = storage.get(key).unwrap();
+        assert_eq!(from_utf8(entry.body[]), Some("cached"));
+        storage.remove(key).unwrap();
+        assert!(storage.get(key).is_none());
+        storage.remove(key).unwrap();
+    }
+}
@@ -0,0 +1,424 @@
+//! Client Cookie Stores
+//!
+//! A `CookieStore` remembers the cookies set by responses, following the
+//! rules of [RFC6265](http://tools.ietf.org/html/rfc6265#section-5.3), and
+//! sends them back with later requests to matching Urls.
+use std::ascii::AsciiExt;
+use std::io::{BufferedReader, File, IoResult, IoError, InvalidInput};
+use std::str::from_utf8;
+use std::sync::{Arc, Mutex};
+
+use cookie::Cookie;
+use time::get_time;
+use url::Url;
+
+use header::Headers;
+use header::common::Cookies;
+
+/// A store of cookies, shared between clones of a `Client`.
+#[deriving(Clone)]
+pub struct CookieStore {
+    inner: Arc<Mutex<Vec<StoredCookie>>>
+}
+
+#[deriving(Clone, PartialEq, Show)]
+struct StoredCookie {
+    name: String,
+    value: String,
+    domain: String,
+    host_only: bool,
+    path: String,
+    secure: bool,
+    // seconds since the epoch, `None` for session cookies
+    expires: Option<i64>,
+}
+
+impl StoredCookie {
+    fn is_expired(&self, now: i64) -> bool {
+        match self.expires {
+            Some(expires) => expires <= now,
+            None => false
+        }
+    }
+
+    fn matches(&self, url: &Url, host: &str) -> bool {
+        let domain_match = if self.host_only {
+            host == self.domain[]
+        } else {
+            domain_matches(host, self.domain[])
+        };
+        let path = url.serialize_path().unwrap_or_else(|| "/".into_string());
+        domain_match &&
+            path_matches(path[], self.path[]) &&
+            (!self.secure || url.scheme[] == "https")
+    }
+}
+
+impl CookieStore {
+    /// Create an empty CookieStore.
+    pub fn new() -> CookieStore {
+        CookieStore {
+            inner: Arc::new(Mutex::new(vec![]))
+        }
+    }
+
+    /// Record the `Set-Cookie` headers of a response to a request for `url`.
+    ///
+    /// Cookies with a `Domain` the Url doesn't belong to are ignored, and
+    /// cookies that are already expired remove any stored cookie of the
+    /// same name, domain and path.
+    pub fn store(&self, url: &Url, headers: &Headers) {
+        let host = match url.serialize_host() {
+            Some(host) => host.to_ascii_lower(),
+            None => return
+        };
+        let raw = match headers.get_raw("Set-Cookie") {
+            Some(raw) => raw,
+            None => return
+        };
+        let now = get_time().sec;
+        let mut cookies = self.inner.lock();
+        for line in raw.iter() {
+            let line = match from_utf8(line[]) {
+                Some(line) => line,
+                None => continue
+            };
+            let cookie = match from_str::<Cookie>(line) {
+                Some(cookie) => cookie,
+                None => continue
+            };
+            let stored = match to_stored(cookie, line, url, host[], now) {
+                Some(stored) => stored,
+                None => {
+                    debug!("rejected cookie from {}: {}", host, line);
+                    continue
+                }
+            };
+
+            cookies.retain(|c| {
+                !(c.name == stored.name && c.domain == stored.domain && c.path == stored.path)
+            });
+            if !stored.is_expired(now) {
+                cookies.push(stored);
+            }
+        }
+    }
+
+    /// The cookies to send with a request for `url`.
+    ///
+    /// Cookies with longer paths are listed first.
+    pub fn cookies_for(&self, url: &Url) -> Vec<Cookie> {
+        let host = match url.serialize_host() {
+            Some(host) => host.to_ascii_lower(),
+            None => return vec![]
+        };
+        let now = get_time().sec;
+        let mut cookies = self.inner.lock();
+        cookies.retain(|c| !c.is_expired(now));
+
+        let mut matching: Vec<&StoredCookie> = cookies.iter()
+            .filter(|c| c.matches(url, host[]))
+            .collect();
+        // sort is stable, so cookies with the same path keep the order
+        // they were set in
+        matching.sort_by(|a, b| b.path.len().cmp(&a.path.len()));
+        matching.iter()
+            .map(|c| Cookie::new(c.name.clone(), c.value.clone()))
+            .collect()
+    }
+
+    /// Add the cookies for `url` to the `Cookie` header in `headers`.
+    ///
+    /// Cookies already in the header take precedence over stored ones with
+    /// the same name.
+    pub fn apply(&self, url: &Url, headers: &mut Headers) {
+        let stored = self.cookies_for(url);
+        if stored.is_empty() {
+            return;
+        }
+        let mut cookies = match headers.get::<Cookies>() {
+            Some(&Cookies(ref cookies)) => cookies.clone(),
+            None => vec![]
+        };
+        for cookie in stored.into_iter() {
+            if !cookies.iter().any(|c| c.name == cookie.name) {
+                cookies.push(cookie);
+            }
+        }
+        headers.set(Cookies(cookies));
+    }
+
+    /// Returns the number of stored cookies.
+    pub fn len(&self) -> uint {
+        self.inner.lock().len()
+    }
+
+    /// Remove all cookies.
+    pub fn clear(&self) {
+        self.inner.lock().clear();
+    }
+
+    /// Write the cookies in the Netscape `cookies.txt` format, as used by
+    /// curl and wget.
+    ///
+    /// Session cookies are written with an expiry of `0`.
+    pub fn write_to(&self, w: &mut Writer) -> IoResult<()> {
+        try!(w.write_line("# Netscape HTTP Cookie File"));
+        for c in self.inner.lock().iter() {
+            let domain = if c.host_only {
+                c.domain.clone()
+            } else {
+                format!(".{}", c.domain)
+            };
+            try!(writeln!(w, "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                          domain,
+                          if c.host_only { "FALSE" } else { "TRUE" },
+                          c.path,
+                          if c.secure { "TRUE" } else { "FALSE" },
+                          c.expires.unwrap_or(0),
+                          c.name,
+                          c.value));
+        }
+        Ok(())
+    }
+
+    /// Read cookies in the Netscape `cookies.txt` format, replacing any
+    /// stored cookies with the same name, domain and path.
+    pub fn read_from<R: Buffer>(&self, r: &mut R) -> IoResult<()> {
+        let now = get_time().sec;
+        let mut cookies = self.inner.lock();
+        for line in r.lines() {
+            let buf = try!(line);
+            let line = buf[].trim_right_chars(['\r', '\n'][]);
+            if line.is_empty() || line.starts_with("#") {
+                continue;
+            }
+            let stored = match parse_line(line) {
+                Some(stored) => stored,
+                None => return Err(IoError {
+                    kind: InvalidInput,
+                    desc: "Invalid line in cookie file",
+                    detail: Some(line.into_string())
+                })
+            };
+            cookies.retain(|c| {
+                !(c.name == stored.name && c.domain == stored.domain && c.path == stored.path)
+            });
+            if !stored.is_expired(now) {
+                cookies.push(stored);
+            }
+        }
+        Ok(())
+    }
+
+    /// Save the cookies to the file at `path`, in the format of `write_to`.
+    pub fn save(&self, path: &Path) -> IoResult<()> {
+        let mut file = try!(File::create(path));
+        self.write_to(&mut file)
+    }
+
+    /// Load a CookieStore from the file at `path`, as written by `save`.
+    pub fn load(path: &Path) -> IoResult<CookieStore> {
+        let store = CookieStore::new();
+        let mut file = BufferedReader::new(try!(File::open(path)));
+        try!(store.read_from(&mut file));
+        Ok(store)
+    }
+}
+
+fn to_stored(cookie: Cookie, raw: &str, url: &Url, host: &str, now: i64) -> Option<StoredCookie> {
+    let (domain, host_only) = match cookie.domain {
+        Some(ref domain) if !domain.is_empty() => {
+            let domain = domain[].trim_left_chars('.').to_ascii_lower();
+            // a cookie may only be set for a domain the host belongs to,
+            // and never for a top-level domain.
+            if !domain_matches(host, domain[]) || !domain[].contains(".") {
+                return None;
+            }
+            (domain, false)
+        },
+        _ => (host.into_string(), true)
+    };
+
+    // the cookie crate fills in a `Path` of `/` when there is none, but
+    // the default should depend on the request path.
+    let path = match cookie.path {
+        Some(ref path) if path[].starts_with("/") && has_attribute(raw, "path") => path.clone(),
+        _ => default_path(url)
+    };
+
+    let expires = match (cookie.max_age, cookie.expires) {
+        (Some(max_age), _) => Some(now + max_age as i64),
+        (None, Some(tm)) => Some(tm.to_timespec().sec),
+        (None, None) => None
+    };
+
+    Some(StoredCookie {
+        name: cookie.name,
+        value: cookie.value,
+        domain: domain,
+        host_only: host_only,
+        path: path,
+        secure: cookie.secure,
+        expires: expires,
+    })
+}
+
+fn parse_line(line: &str) -> Option<StoredCookie> {
+    let fields: Vec<&str> = line.split('\t').collect();
+    if fields.len() != 7 {
+        return None;
+    }
+    let expires: i64 = match from_str(fields[4]) {
+        Some(expires) => expires,
+        None => return None
+    };
+    Some(StoredCookie {
+        name: fields[5].into_string(),
+        value: fields[6].into_string(),
+        domain: fields[0].trim_left_chars('.').to_ascii_lower(),
+        host_only: fields[1] != "TRUE",
+        path: fields[2].into_string(),
+        secure: fields[3] == "TRUE",
+        expires: if expires == 0 { None } else { Some(expires) },
+    })
+}
+
+// Whether a raw `Set-Cookie` value has the attribute `name`.
+fn has_attribute(raw: &str, name: &str) -> bool {
+    raw.split(';').skip(1).any(|attr| {
+        let attr_name = attr.split('=').next().unwrap_or("");
+        attr_name.trim().eq_ignore_ascii_case(name)
+    })
+}
+
+/// The default path of a cookie set by a response to `url`: the path of
+/// the Url, up to but not including its last `/`.
+fn default_path(url: &Url) -> String {
+    let path = url.serialize_path().unwrap_or_else(|| "/".into_string());
+    match path[].rfind('/') {
+        Some(0) | None => "/".into_string(),
+        Some(i) => path[..i].into_string()
+    }
+}
+
+fn domain_matches(host: &str, domain: &str) -> bool {
+    host == domain || host.ends_with(format!(".{}", domain)[])
+}
+
+fn path_matches(path: &str, cookie_path: &str) -> bool {
+    path == cookie_path || (path.starts_with(cookie_path) &&
+        (cookie_path.ends_with("/") || path[cookie_path.len()..].starts_with("/")))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{MemReader, MemWriter};
+    use std::str::from_utf8;
+    use url::Url;
+
+    use header::Headers;
+    use header::common::Cookies;
+    use super::{CookieStore, default_path, path_matches};
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    fn set_cookies(lines: &[&str]) -> Headers {
+        let mut headers = Headers::new();
+        headers.set_raw("Set-Cookie", lines.iter().map(|l| l.as_bytes().to_vec()).collect());
+        headers
+    }
+
+    fn names(store: &CookieStore, u: &str) -> Vec<String> {
+        store.cookies_for(&url(u)).into_iter().map(|c| c.name).collect()
+    }
+
+    #[test]
+    fn test_host_only() {
+        let store = CookieStore::new();
+        store.store(&url("http://example.domain/"), &set_cookies(&["a=1"]));
+        assert_eq!(names(&store, "http://example.domain/"), vec!["a".into_string()]);
+        assert!(names(&store, "http://www.example.domain/").is_empty());
+    }
+
+    #[test]
+    fn test_domain() {
+        let store = CookieStore::new();
+        store.store(&url("http://www.example.domain/"),
+                    &set_cookies(&["a=1; Domain=.example.domain", "b=2; Domain=other.domain",
+                                   "c=3; Domain=domain"]));
+        assert_eq!(store.len(), 1);
+        assert_eq!(names(&store, "http://example.domain/"), vec!["a".into_string()]);
+        assert_eq!(names(&store, "http://api.example.domain/"), vec!["a".into_string()]);
+    }
+
+    #[test]
+    fn test_path() {
+        assert_eq!(default_path(&url("http://example.domain/a/b")), "/a".into_string());
+        assert_eq!(default_path(&url("http://example.domain/a")), "/".into_string());
+        assert!(path_matches("/a/b", "/a"));
+        assert!(path_matches("/a/", "/a/"));
+        assert!(!path_matches("/ab", "/a"));
+
+        let store = CookieStore::new();
+        store.store(&url("http://example.domain/docs/index"),
+                    &set_cookies(&["a=1", "b=2; Path=/"]));
+        assert_eq!(names(&store, "http://example.domain/"), vec!["b".into_string()]);
+        assert_eq!(names(&store, "http://example.domain/docs/x"),
+                   vec!["a".into_string(), "b".into_string()]);
+    }
+
+    #[test]
+    fn test_secure() {
+        let store = CookieStore::new();
+        store.store(&url("https://example.domain/"), &set_cookies(&["a=1; Secure"]));
+        assert!(names(&store, "http://example.domain/").is_empty());
+        assert_eq!(names(&store, "https://example.domain/"), vec!["a".into_string()]);
+    }
+
+    #[test]
+    fn test_expiry() {
+        let store = CookieStore::new();
+        let u = url("http://example.domain/");
+        store.store(&u, &set_cookies(&["a=1; Max-Age=3600", "b=2"]));
+        assert_eq!(store.len(), 2);
+        store.store(&u, &set_cookies(&["a=1; Max-Age=0",
+                                       "b=2; Expires=Thu, 01 Jan 1970 00:00:00 GMT"]));
+        assert_eq!(store.len(), 0);
+    }
+
+    #[test]
+    fn test_apply() {
+        let store = CookieStore::new();
+        let u = url("http://example.domain/");
+        store.store(&u, &set_cookies(&["a=1", "b=2"]));
+        let mut headers = Headers::new();
+        headers.set_raw("Cookie", vec![b"b=mine".to_vec()]);
+        store.apply(&u, &mut headers);
+        let Cookies(ref cookies) = *headers.get::<Cookies>().unwrap();
+        let pairs: Vec<(String, String)> = cookies.iter()
+            .map(|c| (c.name.clone(), c.value.clone()))
+            .collect();
+        assert_eq!(pairs, vec![("b".into_string(), "mine".into_string()),
+                               ("a".into_string(), "1".into_string())]);
+    }
+
+    #[test]
+    fn test_persist() {
+        let store = CookieStore::new();
+        store.store(&url("https://www.example.domain/a/b"),
+                    &set_cookies(&["a=1; Domain=example.domain; Secure; Max-Age=3600", "b=2"]));
+        let mut w = MemWriter::new();
+        store.write_to(&mut w).unwrap();
+        let written = from_utf8(w.get_ref()).unwrap().into_string();
+        assert!(written[].contains("\nwww.example.domain\tFALSE\t/a\tFALSE\t0\tb\t2\n"));
+
+        let loaded = CookieStore::new();
+        loaded.read_from(&mut MemReader::new(written.into_bytes())).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(names(&loaded, "https://api.example.domain/a/x"), vec!["a".into_string()]);
+        assert_eq!(names(&loaded, "http://www.example.domain/a/"), vec!["b".into_string()]);
+    }
+}
@@ -0,0 +1,301 @@
+//! Resumable Downloads
+//!
+//! A `Download` writes a resource to a file. When the connection drops
+//! before the end, it picks up where it stopped with a `Range` request,
+//! rather than starting over:
+//!
+//! ```no_run
+//! # use hyper::Url;
+//! # use hyper::client::Client;
+//! # use hyper::client::download::Outcome;
+//! let client = Client::new();
+//! let url = Url::parse("http://example.domain/release.iso").unwrap();
+//! match client.download(url, Path::new("release.iso")).send() {
+//!     Ok(Outcome::Complete(len)) => println!("downloaded {} bytes", len),
+//!     Ok(Outcome::Unexpected(res)) => println!("the server answered {}", res.status),
+//!     Err(e) => println!("download failed: {}", e)
+//! }
+//! ```
+//!
+//! Until the download is complete, the `ETag` or `Last-Modified` of the
+//! resource is kept next to the file, in a file with `.resume` added to its
+//! name. Resuming sends it in an `If-Range`, so that a resource that
+//! changed in the meantime is downloaded whole again, rather than spliced.
+//! A `Download` of the same Url and path, even in another process, resumes
+//! a download that failed.
+use std::error::FromError;
+use std::io::{File, IoResult, EndOfFile, FileNotFound, Open, Write, SeekSet,
+              standard_error};
+use std::io::fs::{unlink, PathExtensions};
+use std::io::timer::sleep;
+
+use url::Url;
+
+use client::{Client, Response, RetryPolicy};
+use client::retry::is_transient;
+use header::Headers;
+use header::common::{ContentLength, ContentRange, Date, ETag, IfRange, LastModified, Range,
+                     TransferEncoding};
+use header::common::range::ByteRange::AllFrom;
+use header::common::transfer_encoding::Encoding::Chunked;
+use method::Method::Get;
+use net::{NetworkConnector, NetworkStream};
+use status::StatusCode;
+use HttpError::HttpIoError;
+use HttpResult;
+
+use self::Outcome::{Complete, Unexpected};
+
+/// How a download ended.
+pub enum Outcome {
+    /// The whole resource is in the file, which is this long.
+    Complete(u64),
+    /// The server answered with something else than the resource, such as
+    /// a `404 Not Found`. The response is unread, and the file untouched.
+    Unexpected(Response),
+}
+
+/// A download of a resource into a file.
+pub struct Download<'a, C: 'a> {
+    client: &'a Client<C>,
+    url: Url,
+    path: Path,
+    retry_policy: RetryPolicy,
+}
+
+impl<'a, C: NetworkConnector<S>, S: NetworkStream> Download<'a, C> {
+    /// A download of `url` into the file at `path`, through `client`.
+    ///
+    /// It is attempted up to 3 times, as with `RetryPolicy::new()`.
+    pub fn new(client: &'a Client<C>, url: Url, path: Path) -> Download<'a, C> {
+        Download {
+            client: client,
+            url: url,
+            path: path,
+            retry_policy: RetryPolicy::new(),
+        }
+    }
+
+    /// Set how many attempts are made, and how long to wait between them.
+    ///
+    /// Every attempt after the first resumes from the end of the file,
+    /// when the server sent a validator.
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry_policy = policy;
+    }
+
+    /// The path of the file the resource is written to.
+    #[inline]
+    pub fn path(&self) -> &Path { &self.path }
+
+    /// Download the resource, resuming after interruptions.
+    ///
+    /// A body cut short, or a connection that fails, is attempted again
+    /// according to the retry policy, and fails with the last error once
+    /// there are no attempts left. The Client's timeouts apply to each
+    /// attempt.
+    pub fn send(&self) -> HttpResult<Outcome> {
+        let mut attempts = 0u;
+        loop {
+            attempts += 1;
+            let err = match self.attempt(true) {
+                Ok(outcome) => return Ok(outcome),
+                Err(e) => e
+            };
+            if attempts >= self.retry_policy.max_attempts() || !is_transient(&err) {
+                return Err(err);
+            }
+            let delay = self.retry_policy.backoff(attempts);
+            debug!("download of {} interrupted: {}, resuming in {}", self.url, err, delay);
+            sleep(delay);
+        }
+    }
+
+    // Send a request for what's missing from the file, and write the
+    // response to it. Unless `restart` is false, a server that can't send
+    // the missing range is asked for the whole resource instead.
+    fn attempt(&self, restart: bool) -> HttpResult<Outcome> {
+        let validator = self.validator();
+        let offset = match validator {
+            Some(_) if self.path.exists() => try!(self.path.stat()).size,
+            _ => 0
+        };
+
+        let mut req = try!(self.client.request(Get, self.url.clone()));
+        // ranges are of the body as it is sent, so it's stored as it is
+        req.set_decompress(false);
+        if let Some(validator) = validator {
+            if offset > 0 {
+                debug!("resuming {} from {}", self.url, offset);
+                req.headers_mut().set(Range(vec![AllFrom(offset)]));
+                req.headers_mut().set(validator);
+            }
+        }
+        let res = try!(try!(req.start()).send());
+
+        match res.status {
+            StatusCode::Ok => {
+                let len = res.headers.get::<ContentLength>().map(|len| len.len() as u64);
+                try!(self.save_validator(&res.headers));
+                let file = try!(File::create(&self.path));
+                self.write(res, file, 0, len)
+            },
+            StatusCode::PartialContent if offset > 0 => {
+                let range = res.headers.get::<ContentRange>().map(|range| range.clone());
+                match range {
+                    Some(ContentRange { range: Some((first, last)), complete_length })
+                            if first == offset => {
+                        let len = complete_length.unwrap_or(last + 1);
+                        let mut file = try!(File::open_mode(&self.path, Open, Write));
+                        try!(file.seek(offset as i64, SeekSet));
+                        self.write(res, file, offset, Some(len))
+                    },
+                    _ if restart => {
+                        debug!("unexpected Content-Range {}, starting over", range);
+                        try!(self.start_over());
+                        self.attempt(false)
+                    },
+                    _ => Ok(Unexpected(res))
+                }
+            },
+            StatusCode::RequestedRangeNotSatisfiable if offset > 0 => {
+                let length = res.headers.get::<ContentRange>()
+                    .and_then(|range| range.complete_length);
+                if length == Some(offset) {
+                    // the interruption came after the last byte
+                    try!(self.remove_validator());
+                    Ok(Complete(offset))
+                } else if restart {
+                    debug!("range not satisfiable, starting over");
+                    try!(self.start_over());
+                    self.attempt(false)
+                } else {
+                    Ok(Unexpected(res))
+                }
+            },
+            _ => Ok(Unexpected(res))
+        }
+    }
+
+    // Write the body of `res` to `file`, from `offset`. A body shorter than
+    // announced leaves what was received in the file, to be resumed.
+    fn write(&self, mut res: Response, mut file: File, offset: u64,
+             len: Option<u64>) -> HttpResult<Outcome> {
+        let mut written = offset;
+        let mut buf = [0u8, ..8192];
+        loop {
+            let n = match res.read(&mut buf) {
+                Ok(n) => n,
+                Err(ref e) if e.kind == EndOfFile => break,
+                Err(e) => return Err(FromError::from_error(e))
+            };
+            try!(file.write(buf[..n]));
+            written += n as u64;
+        }
+        try!(file.flush());
+
+        let chunked = res.headers.get::<TransferEncoding>()
+            .map_or(false, |te| te.0.contains(&Chunked));
+        let complete = match len {
+            Some(len) => written >= len,
+            None if chunked => res.trailers().is_some(),
+            None => true
+        };
+        if !complete {
+            debug!("download of {} cut short at {} bytes", self.url, written);
+            return Err(HttpIoError(standard_error(EndOfFile)));
+        }
+        try!(self.remove_validator());
+        Ok(Complete(written))
+    }
+
+    fn resume_path(&self) -> Path {
+        let mut name = self.path.filename().unwrap_or(b"download").to_vec();
+        name.push_all(b".resume");
+        self.path.with_filename(name)
+    }
+
+    // The `If-Range` to resume with, if the file can be resumed.
+    fn validator(&self) -> Option<IfRange> {
+        File::open(&self.resume_path()).read_to_string().ok()
+            .and_then(|s| from_str::<IfRange>(s[]))
+    }
+
+    // Keep the strong `ETag` of a response, or else its `Last-Modified`,
+    // for resuming.
+    fn save_validator(&self, headers: &Headers) -> IoResult<()> {
+        let validator = match (headers.get::<ETag>(), headers.get::<LastModified>()) {
+            (Some(etag), _) if !etag.is_weak() => IfRange::EntityTag(etag.0.clone()),
+            (_, Some(&LastModified(tm))) => IfRange::Date(tm),
+            _ => return self.remove_validator()
+        };
+        let value = match validator {
+            IfRange::EntityTag(tag) => tag,
+            IfRange::Date(tm) => Date(tm).to_string()
+        };
+        File::create(&self.resume_path()).write_str(value[])
+    }
+
+    fn remove_validator(&self) -> IoResult<()> {
+        match unlink(&self.resume_path()) {
+            Err(ref e) if e.kind == FileNotFound => Ok(()),
+            result => result
+        }
+    }
+
+    fn start_over(&self) -> IoResult<()> {
+        try!(self.remove_validator());
+        File::create(&self.path).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{File, TempDir};
+    use std::io::fs::PathExtensions;
+    use url::Url;
+
+    use client::Client;
+    use mock::ScriptedConnector;
+    use super::Outcome::Complete;
+
+    fn download(connector: &ScriptedConnector, path: &Path) -> u64 {
+        let client = Client::with_connector(connector.clone());
+        let url = Url::parse("http://example.domain/file").unwrap();
+        match client.download(url, path.clone()).send() {
+            Ok(Complete(len)) => len,
+            _ => panic!("download failed")
+        }
+    }
+
+    #[test]
+    fn test_resume() {
+        let connector = ScriptedConnector::new(&[
+            b"HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: 10\r\n\r\n01234",
+            b"HTTP/1.1 206 Partial Content\r\nContent-Range: bytes 5-9/10\r\n\
+              Content-Length: 5\r\n\r\n56789",
+        ]);
+        // the directory is removed as it's dropped, even if the test fails
+        let dir = TempDir::new("hyper-download-test").unwrap();
+        let path = dir.path().join("file");
+        assert_eq!(download(&connector, &path), 10);
+        assert_eq!(File::open(&path).read_to_end(), Ok(b"0123456789".to_vec()));
+
+        let written = connector.written();
+        assert!(written[].contains("Range: bytes=5-\r\n"));
+        assert!(written[].contains("If-Range: \"v1\"\r\n"));
+        assert!(!Path::new(format!("{}.resume", path.display())).exists());
+    }
+
+    #[test]
+    fn test_range_ignored() {
+        let connector = ScriptedConnector::new(&[
+            b"HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: 10\r\n\r\n01234",
+            b"HTTP/1.1 200 OK\r\nETag: \"v2\"\r\nContent-Length: 3\r\n\r\nabc",
+        ]);
+        let dir = TempDir::new("hyper-download-test").unwrap();
+        let path = dir.path().join("file");
+        assert_eq!(download(&connector, &path), 3);
+        assert_eq!(File::open(&path).read_to_end(), Ok(b"abc".to_vec()));
+    }
+}
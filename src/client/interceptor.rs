@@ -0,0 +1,194 @@
+//! Client Interceptors
+//!
+//! Interceptors add behavior to every request a `Client` sends, such as
+//! injecting tokens and request IDs, or recording metrics:
+//!
+//! ```no_run
+//! # use hyper::Url;
+//! # use hyper::client::{Client, Interceptor};
+//! # use hyper::header::Headers;
+//! # use hyper::method::Method;
+//! struct RequestId;
+//!
+//! impl Interceptor for RequestId {
+//!     fn before(&self, _method: &mut Method, _url: &mut Url, headers: &mut Headers)
+//!               -> Option<hyper::client::Response> {
+//!         headers.set_raw("X-Request-Id", vec![b"42".to_vec()]);
+//!         None
+//!     }
+//! }
+//!
+//! let mut client = Client::new();
+//! client.add_interceptor(RequestId);
+//! ```
+//!
+//! Interceptors run in the order they were added before a request is
+//! started, and in the reverse order once its response has been received,
+//! so the first one added sees the request first and the response last.
+use std::sync::Arc;
+
+use url::Url;
+
+use header::Headers;
+use method::Method;
+use client::Response;
+use HttpResult;
+
+/// Looks at, and changes, every request sent by a `Client`, and its
+/// response.
+///
+/// Each attempt to send a request goes through the interceptors, including
+/// retries, redirects and answers to authentication challenges.
+pub trait Interceptor: Send + Sync {
+    /// Change the method, url or headers of a request before it is started.
+    ///
+    /// Returning a `Response` answers the request without sending it, and
+    /// without asking the interceptors after this one.
+    #[allow(unused_variables)]
+    fn before(&self, method: &mut Method, url: &mut Url,
+              headers: &mut Headers) -> Option<Response> {
+        None
+    }
+
+    /// Inspect the response to a request, or replace it.
+    ///
+    /// An error is returned from `send()` as it is.
+    #[allow(unused_variables)]
+    fn after(&self, method: &Method, url: &Url, res: Response) -> HttpResult<Response> {
+        Ok(res)
+    }
+}
+
+impl_for_box!(Interceptor {
+    fn before(&self, method: &mut Method, url: &mut Url,
+              headers: &mut Headers) -> Option<Response>;
+    fn after(&self, method: &Method, url: &Url, res: Response) -> HttpResult<Response>;
+})
+
+/// An ordered chain of interceptors.
+///
+/// Clones share the interceptors added before they were cloned.
+#[deriving(Clone)]
+pub struct Chain {
+    interceptors: Vec<Arc<Box<Interceptor + Send + Sync>>>,
+}
+
+impl Chain {
+    /// An empty chain.
+    pub fn new() -> Chain {
+        Chain { interceptors: vec![] }
+    }
+
+    /// Add an interceptor to the end of the chain.
+    pub fn push<I: Interceptor>(&mut self, interceptor: I) {
+        self.interceptors.push(Arc::new(box interceptor as Box<Interceptor + Send + Sync>));
+    }
+
+    /// The number of interceptors in the chain.
+    #[inline]
+    pub fn len(&self) -> uint { self.interceptors.len() }
+
+    /// Send a request through the chain, calling `send` with the method,
+    /// url and headers the interceptors left, unless one of them answers
+    /// the request itself.
+    pub fn run(&self, mut method: Method, mut url: Url, mut headers: Headers,
+               send: |Method, Url, Headers| -> HttpResult<Response>) -> HttpResult<Response> {
+        let mut reached = 0;
+        let mut answer = None;
+        for interceptor in self.interceptors.iter() {
+            reached += 1;
+            answer = interceptor.before(&mut method, &mut url, &mut headers);
+            if answer.is_some() {
+                debug!("request answered by an interceptor");
+                break;
+            }
+        }
+
+        let mut res = match answer {
+            Some(res) => {
+                // the interceptor that answered doesn't see its own answer
+                reached -= 1;
+                res
+            },
+            None => try!(send(method.clone(), url.clone(), headers))
+        };
+        for interceptor in self.interceptors[..reached].iter().rev() {
+            res = try!(interceptor.after(&method, &url, res));
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use url::Url;
+
+    use client::Response;
+    use header::Headers;
+    use header::common::ContentLength;
+    use method::Method::{mod, Get, Post};
+    use status::StatusCode;
+    use HttpResult;
+    use super::{Chain, Interceptor};
+
+    struct Recorder {
+        name: &'static str,
+        log: Arc<Mutex<Vec<String>>>,
+        answer: bool,
+    }
+
+    impl Interceptor for Recorder {
+        fn before(&self, method: &mut Method, _url: &mut Url,
+                  headers: &mut Headers) -> Option<Response> {
+            self.log.lock().push(format!("before {}", self.name));
+            *method = Post;
+            headers.set(ContentLength(0));
+            if self.answer {
+                Some(Response::synthesized(StatusCode::NoContent, Headers::new(), vec![]))
+            } else {
+                None
+            }
+        }
+
+        fn after(&self, _method: &Method, _url: &Url, res: Response) -> HttpResult<Response> {
+            self.log.lock().push(format!("after {}", self.name));
+            Ok(res)
+        }
+    }
+
+    fn chain(log: &Arc<Mutex<Vec<String>>>, answer_at: Option<uint>) -> Chain {
+        let mut chain = Chain::new();
+        for (i, &name) in ["a", "b", "c"].iter().enumerate() {
+            chain.push(Recorder { name: name, log: log.clone(), answer: answer_at == Some(i) });
+        }
+        chain
+    }
+
+    #[test]
+    fn test_order() {
+        let log = Arc::new(Mutex::new(vec![]));
+        let url = Url::parse("http://example.domain/").unwrap();
+        let res = chain(&log, None).run(Get, url, Headers::new(), |method, _url, headers| {
+            assert_eq!(method, Post);
+            assert!(headers.has::<ContentLength>());
+            Ok(Response::synthesized(StatusCode::Ok, Headers::new(), b"ok".to_vec()))
+        }).unwrap();
+        assert_eq!(res.status, StatusCode::Ok);
+        assert_eq!(*log.lock(), vec!["before a", "before b", "before c",
+                                     "after c", "after b", "after a"]
+                                .into_iter().map(|s| s.into_string()).collect::<Vec<String>>());
+    }
+
+    #[test]
+    fn test_short_circuit() {
+        let log = Arc::new(Mutex::new(vec![]));
+        let url = Url::parse("http://example.domain/").unwrap();
+        let res = chain(&log, Some(1)).run(Get, url, Headers::new(), |_method, _url, _headers| {
+            panic!("the request shouldn't be sent");
+        }).unwrap();
+        assert_eq!(res.status, StatusCode::NoContent);
+        assert_eq!(*log.lock(), vec!["before a", "before b", "after a"]
+                                .into_iter().map(|s| s.into_string()).collect::<Vec<String>>());
+    }
+}
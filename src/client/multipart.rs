@@ -0,0 +1,240 @@
+//! Client Multipart Bodies
+//!
+//! A `Multipart` builds a `multipart/form-data` body out of text fields and
+//! streamed files, and is read like any other `Reader`:
+//!
+//! ```no_run
+//! # use hyper::Url;
+//! # use hyper::client::Client;
+//! # use hyper::client::multipart::Multipart;
+//! # use std::io::File;
+//! let file = File::open(&Path::new("build.tar.gz"));
+//! let mut form = Multipart::new();
+//! form.add_text("version", "1.0");
+//! form.add_stream("artifact", file, Some("build.tar.gz"), None, None);
+//!
+//! let client = Client::new();
+//! let res = client.post(Url::parse("http://example.domain/upload").unwrap())
+//!     .multipart(&mut form)
+//!     .send();
+//! ```
+use std::io::{IoResult, EndOfFile, MemReader, standard_error};
+use std::rand::{task_rng, Rng};
+
+use serialize::hex::ToHex;
+
+use header::Headers;
+use header::common::{ContentDisposition, ContentLength, ContentType};
+use mime::{Mime, Multipart as MultipartType, FormData, AttrExt, ValueExt};
+
+use self::State::{Head, Body, Tail, Done};
+
+/// A `multipart/form-data` body.
+pub struct Multipart<'a> {
+    boundary: String,
+    parts: Vec<Part<'a>>,
+    state: State,
+    // bytes between the bodies of the parts, waiting to be read
+    pending: MemReader,
+}
+
+/// A part of a `Multipart` body.
+pub struct Part<'a> {
+    headers: Headers,
+    body: Box<Reader + 'a>,
+    len: Option<uint>,
+}
+
+enum State {
+    Head(uint),
+    Body(uint),
+    Tail,
+    Done,
+}
+
+impl<'a> Part<'a> {
+    /// A part with `headers`, reading its body from `body`.
+    ///
+    /// Without the `len` of the body, the length of the whole `Multipart`
+    /// isn't known either, and it has to be sent chunked.
+    pub fn new<R: Reader + 'a>(headers: Headers, body: R, len: Option<uint>) -> Part<'a> {
+        Part {
+            headers: headers,
+            body: box body as Box<Reader + 'a>,
+            len: len,
+        }
+    }
+
+    /// Get a reference to the headers of this part.
+    #[inline]
+    pub fn headers(&self) -> &Headers { &self.headers }
+}
+
+impl<'a> Multipart<'a> {
+    /// An empty body, with a random boundary.
+    pub fn new() -> Multipart<'a> {
+        let random = task_rng().gen_iter::<u8>().take(16).collect::<Vec<u8>>().to_hex();
+        Multipart::with_boundary(format!("------------------------{}", random)[])
+    }
+
+    /// An empty body, with parts delimited by `boundary`.
+    ///
+    /// The boundary must not occur in any of the parts.
+    pub fn with_boundary(boundary: &str) -> Multipart<'a> {
+        Multipart {
+            boundary: boundary.into_string(),
+            parts: vec![],
+            state: Head(0),
+            pending: MemReader::new(vec![]),
+        }
+    }
+
+    /// The boundary delimiting the parts.
+    #[inline]
+    pub fn boundary(&self) -> &str { self.boundary[] }
+
+    /// Add a text field.
+    pub fn add_text(&mut self, name: &str, value: &str) {
+        let mut headers = Headers::new();
+        headers.set(ContentDisposition::form_data(name));
+        let bytes = value.as_bytes().to_vec();
+        let len = bytes.len();
+        self.add_part(Part::new(headers, MemReader::new(bytes), Some(len)));
+    }
+
+    /// Add a field streamed from `reader`, such as a file.
+    ///
+    /// The `filename` and `Content-Type`, if given, are sent in the headers
+    /// of the part. Files are usually sent as `application/octet-stream` if
+    /// their type isn't known.
+    pub fn add_stream<R: Reader + 'a>(&mut self, name: &str, reader: R, filename: Option<&str>,
+                                      content_type: Option<Mime>, len: Option<uint>) {
+        let mut disposition = ContentDisposition::form_data(name);
+        if let Some(filename) = filename {
+            disposition.params.push(("filename".into_string(), filename.into_string()));
+        }
+        let mut headers = Headers::new();
+        headers.set(disposition);
+        if let Some(mime) = content_type {
+            headers.set(ContentType(mime));
+        }
+        self.add_part(Part::new(headers, reader, len));
+    }
+
+    /// Add a part with any headers.
+    pub fn add_part(&mut self, part: Part<'a>) {
+        self.parts.push(part);
+    }
+
+    /// The `multipart/form-data` content type, with the boundary.
+    pub fn content_type(&self) -> ContentType {
+        ContentType(Mime(MultipartType, FormData,
+                         vec![(AttrExt("boundary".into_string()), ValueExt(self.boundary.clone()))]))
+    }
+
+    /// The length of the whole body, if every part knows its length.
+    pub fn len(&self) -> Option<uint> {
+        let delimiter = self.boundary.len() + 4;
+        let mut len = delimiter + 2;
+        for part in self.parts.iter() {
+            match part.len {
+                Some(body) => {
+                    len += delimiter + part.headers.to_string().len() + 2 + body + 2;
+                },
+                None => return None
+            }
+        }
+        Some(len)
+    }
+
+    /// Set the `Content-Type` of a request to this body, and its
+    /// `Content-Length` if it's known. Without one, the request body is
+    /// sent chunked.
+    pub fn apply(&self, headers: &mut Headers) {
+        headers.set(self.content_type());
+        match self.len() {
+            Some(len) => headers.set(ContentLength(len)),
+            None => {
+                headers.remove::<ContentLength>();
+            }
+        }
+    }
+
+    fn next(&mut self) {
+        let (state, pending) = match self.state {
+            Head(i) if i < self.parts.len() => {
+                let head = format!("--{}\r\n{}\r\n", self.boundary, self.parts[i].headers);
+                (Body(i), head.into_bytes())
+            },
+            Head(_) => (Tail, vec![]),
+            Body(i) => (Head(i + 1), b"\r\n".to_vec()),
+            Tail => (Done, format!("--{}--\r\n", self.boundary).into_bytes()),
+            Done => (Done, vec![])
+        };
+        self.state = state;
+        self.pending = MemReader::new(pending);
+    }
+}
+
+impl<'a> Reader for Multipart<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        loop {
+            match self.pending.read(buf) {
+                Ok(n) => return Ok(n),
+                Err(ref e) if e.kind == EndOfFile => (),
+                Err(e) => return Err(e)
+            }
+            match self.state {
+                Body(i) => match self.parts[i].body.read(buf) {
+                    Ok(n) => return Ok(n),
+                    Err(ref e) if e.kind == EndOfFile => self.next(),
+                    Err(e) => return Err(e)
+                },
+                Done => return Err(standard_error(EndOfFile)),
+                _ => self.next()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::MemReader;
+
+    use header::Headers;
+    use mime::{Mime, Text, Plain};
+    use super::Multipart;
+
+    #[test]
+    fn test_multipart() {
+        let mut form = Multipart::with_boundary("XyZ");
+        form.add_text("name", "value");
+        form.add_stream("file", MemReader::new(b"contents".to_vec()), Some("a.txt"),
+                        Some(Mime(Text, Plain, vec![])), Some(8));
+
+        let len = form.len().unwrap();
+        let body = String::from_utf8(form.read_to_end().unwrap()).unwrap();
+        assert!(body[].starts_with("\
+            --XyZ\r\n\
+            Content-Disposition: form-data; name=\"name\"\r\n\
+            \r\n\
+            value\r\n\
+            --XyZ\r\n"));
+        // the order of the headers of a part isn't fixed
+        assert!(body[].contains("Content-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\n"));
+        assert!(body[].contains("Content-Type: text/plain\r\n"));
+        assert!(body[].ends_with("\r\n\r\ncontents\r\n--XyZ--\r\n"));
+        assert_eq!(len, body.len());
+    }
+
+    #[test]
+    fn test_unknown_length() {
+        let mut form = Multipart::new();
+        form.add_stream("file", MemReader::new(vec![]), None, None, None);
+        let mut headers = Headers::new();
+        form.apply(&mut headers);
+        assert!(headers.get_raw("Content-Length").is_none());
+        assert_eq!(headers.get_raw("Content-Type").unwrap()[0],
+                   format!("multipart/form-data; boundary={}", form.boundary()).into_bytes());
+    }
+}
@@ -0,0 +1,276 @@
+//! Client Pipelining
+//!
+//! HTTP/1.1 allows a client to write several requests to a connection
+//! without waiting for their responses, and read the responses afterwards,
+//! in the order the requests were written. Against a single host, this
+//! saves a round trip for every request but the first:
+//!
+//! ```no_run
+//! # use hyper::Url;
+//! # use hyper::client::Client;
+//! let client = Client::new();
+//! let mut pipeline = client.pipeline();
+//! for id in range(0u, 10) {
+//!     pipeline.get(Url::parse(format!("http://example.domain/items/{}", id)[]).unwrap());
+//! }
+//! for res in pipeline.send().unwrap().iter_mut() {
+//!     println!("{}", res.read_to_string().unwrap());
+//! }
+//! ```
+//!
+//! Since the responses share the connection, each one is read whole, body
+//! included, before the next one. Only idempotent requests can be
+//! pipelined: when the server closes the connection midway, the requests
+//! it didn't answer are sent again on a new connection.
+use std::any::AnyRefExt;
+use std::io::BufferedReader;
+
+use url::Url;
+
+use client::{Client, Response};
+use client::pool::PooledStream;
+use client::redirect::same_origin;
+use client::retry::is_transient;
+use client::timeout::Deadline;
+use header::Headers;
+use http::{read_status_line, response_reader, should_keep_alive};
+use http::HttpReader::EofReader;
+use method::Method::{mod, Get, Head};
+use net::{NetworkConnector, NetworkStream};
+use HttpError::{mod, HttpMethodError, HttpUriError};
+use HttpResult;
+
+/// Requests to send pipelined on one connection.
+pub struct Pipeline<'a, C: 'a> {
+    client: &'a Client<C>,
+    requests: Vec<(Method, Url)>,
+}
+
+impl<'a, C: NetworkConnector<S>, S: NetworkStream> Pipeline<'a, C> {
+    /// An empty pipeline, to send through `client`.
+    pub fn new(client: &'a Client<C>) -> Pipeline<'a, C> {
+        Pipeline {
+            client: client,
+            requests: vec![],
+        }
+    }
+
+    /// Add a request to the end of the pipeline.
+    ///
+    /// Every request must have an idempotent method, and have the same
+    /// scheme, host and port as the first one.
+    pub fn add(&mut self, method: Method, url: Url) {
+        self.requests.push((method, url));
+    }
+
+    /// Add a GET request to the end of the pipeline.
+    #[inline]
+    pub fn get(&mut self, url: Url) { self.add(Get, url) }
+
+    /// Add a HEAD request to the end of the pipeline.
+    #[inline]
+    pub fn head(&mut self, url: Url) { self.add(Head, url) }
+
+    /// The number of requests in the pipeline.
+    #[inline]
+    pub fn len(&self) -> uint { self.requests.len() }
+
+    /// Send the requests, and read their responses, in order.
+    ///
+    /// Fails with `HttpMethodError` if a request isn't idempotent, and with
+    /// `HttpUriError` if the requests aren't all to the same origin. The
+    /// Client's timeouts apply, with the `total` deadline covering the
+    /// whole pipeline.
+    pub fn send(self) -> HttpResult<Vec<Response>> {
+        if let Some(&(_, ref first)) = self.requests.as_slice().get(0) {
+            for &(ref method, ref url) in self.requests.iter() {
+                if !method.idempotent() {
+                    return Err(HttpMethodError);
+                }
+                if !same_origin(first, url) {
+                    return Err(HttpUriError);
+                }
+            }
+        }
+
+        let deadline = self.client.timeouts.total.map(Deadline::after);
+        let mut responses = Vec::with_capacity(self.requests.len());
+        // rounds in a row that got no response at all
+        let mut stalled = 0u;
+        while responses.len() < self.requests.len() {
+            let answered = responses.len();
+            match self.round(self.requests[answered..], deadline.clone(), &mut responses) {
+                Some(err) => {
+                    if !is_transient(&err) {
+                        return Err(err);
+                    }
+                    stalled = if responses.len() == answered { stalled + 1 } else { 0 };
+                    // a pooled connection may have been stale, but a new
+                    // one failing too means the server is gone
+                    if stalled > 1 {
+                        return Err(err);
+                    }
+                    debug!("pipeline interrupted after {} of {} responses: {}",
+                           responses.len(), self.requests.len(), err);
+                },
+                None => ()
+            }
+        }
+        Ok(responses)
+    }
+
+    // Write `pending` to one connection, and read as many of the responses
+    // as the connection yields. Returns the error that cut it short, if any.
+    fn round(&self, pending: &[(Method, Url)], deadline: Option<Deadline>,
+             responses: &mut Vec<Response>) -> Option<HttpError> {
+        let client = self.client;
+        let stream = match client.connection(&pending[0].1, &client.timeouts, deadline) {
+            Ok(stream) => stream,
+            Err(e) => return Some(e)
+        };
+
+        let mut written = 0u;
+        let mut error = None;
+        for &(ref method, ref url) in pending.iter() {
+            // clones share the connection, which is read from below
+            let result = client.request_on(method.clone(), url.clone(), stream.clone(),
+                                           &client.timeouts)
+                .and_then(|req| req.start())
+                .and_then(|req| req.finish());
+            match result {
+                Ok(_) => written += 1,
+                Err(e) => {
+                    error = Some(e);
+                    break;
+                }
+            }
+        }
+        debug!("pipelined {} requests", written);
+
+        let mut stream = BufferedReader::new(stream);
+        for &(ref method, ref url) in pending[..written].iter() {
+            let (mut res, keep_alive) = match read_response(&mut stream, method) {
+                Ok(res) => res,
+                Err(e) => return Some(e)
+            };
+            if let Some(ref store) = client.cookie_store {
+                store.store(url, &res.headers);
+            }
+            if *method != Head {
+                res.decompress();
+            }
+            responses.push(res);
+            if !keep_alive {
+                // the rest are sent again on a new connection
+                debug!("server closes the pipelined connection");
+                return None;
+            }
+        }
+
+        if error.is_none() {
+            let stream = stream.into_inner();
+            let stream: &NetworkStream = &*stream;
+            if let Some(pooled) = stream.downcast_ref::<PooledStream>() {
+                pooled.set_reusable(true);
+            }
+        }
+        error
+    }
+}
+
+// Read the next response on a pipelined connection, body and all, and
+// whether the connection carries on after it.
+fn read_response(stream: &mut BufferedReader<Box<NetworkStream + Send>>,
+                 method: &Method) -> HttpResult<(Response, bool)> {
+    let mut head;
+    loop {
+        let (version, raw_status) = try!(read_status_line(stream));
+        let headers = try!(Headers::from_raw(stream));
+        if raw_status.0 / 100 != 1 || raw_status.0 == 101 {
+            head = (version, raw_status, headers);
+            break;
+        }
+        debug!("skipping interim {} response", raw_status.0);
+    }
+    let (version, raw_status, headers) = head;
+
+    let mut reader = try!(response_reader(stream.by_ref(), method, raw_status.0, &headers));
+    let keep_alive = match reader {
+        EofReader(_) => false,
+        _ => raw_status.0 != 101 && should_keep_alive(version, &headers)
+    };
+
+    // a body cut short fails with an incomplete message error
+    let body = try!(reader.read_to_end());
+    let trailers = reader.trailers().map(|trailers| trailers.clone());
+
+    let res = try!(Response::buffered(version, raw_status, headers, body, trailers));
+    Ok((res, keep_alive))
+}
+
+#[cfg(test)]
+mod tests {
+    use url::Url;
+
+    use client::Client;
+    use method::Method::Post;
+    use mock::ScriptedConnector;
+    use HttpError::{HttpMethodError, HttpUriError};
+
+    fn url(path: &str) -> Url {
+        Url::parse(format!("http://example.domain{}", path)[]).unwrap()
+    }
+
+    #[test]
+    fn test_pipeline() {
+        let connector = ScriptedConnector::new(&[
+            b"HTTP/1.1 200 OK\r\nContent-Length: 1\r\n\r\na",
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n1\r\nb\r\n0\r\n\r\n",
+            b"HTTP/1.1 200 OK\r\nContent-Length: 1\r\n\r\nc",
+        ]);
+        let client = Client::with_connector(connector.clone());
+        let mut pipeline = client.pipeline();
+        for path in ["/a", "/b", "/c"].iter() {
+            pipeline.get(url(*path));
+        }
+        let mut responses = pipeline.send().unwrap();
+        let bodies: Vec<String> = responses.iter_mut()
+            .map(|res| res.read_to_string().unwrap())
+            .collect();
+        assert_eq!(bodies, vec!["a".into_string(), "b".into_string(), "c".into_string()]);
+        assert_eq!(connector.connects().len(), 1);
+        let key = ("example.domain".into_string(), 80, "http".into_string());
+        assert_eq!(client.pool().idle_count(&key), 1);
+    }
+
+    #[test]
+    fn test_pipeline_retries_unanswered() {
+        let connector = ScriptedConnector::new(&[
+            b"HTTP/1.1 200 OK\r\nContent-Length: 1\r\nConnection: close\r\n\r\na",
+            b"",
+            b"HTTP/1.1 200 OK\r\nContent-Length: 1\r\n\r\nb",
+        ]);
+        let client = Client::with_connector(connector.clone());
+        let mut pipeline = client.pipeline();
+        pipeline.get(url("/a"));
+        pipeline.get(url("/b"));
+        let mut responses = pipeline.send().unwrap();
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[1].read_to_string(), Ok("b".into_string()));
+        assert_eq!(connector.connects().len(), 2);
+        assert_eq!(connector.written()[].split_str("GET /b").count(), 3);
+    }
+
+    #[test]
+    fn test_pipeline_rejects() {
+        let client = Client::with_connector(ScriptedConnector::new(&[b""]));
+        let mut pipeline = client.pipeline();
+        pipeline.add(Post, url("/"));
+        assert_eq!(pipeline.send().err(), Some(HttpMethodError));
+
+        let mut pipeline = client.pipeline();
+        pipeline.get(url("/"));
+        pipeline.get(Url::parse("http://other.domain/").unwrap());
+        assert_eq!(pipeline.send().err(), Some(HttpUriError));
+    }
+}
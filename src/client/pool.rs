@@ -0,0 +1,366 @@
+//! Client Connection Pooling
+//!
+//! A `Pool` keeps idle keep-alive connections around, keyed by host, port
+//! and scheme, so that later requests to the same origin can skip the TCP
+//! (and TLS) handshake.
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::collections::hash_map::{Occupied, Vacant};
+use std::default::Default;
+use std::io::IoResult;
+use std::io::net::ip::{Port, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use time::precise_time_ns;
+
+use client::timeout::{Deadline, effective};
+use net::NetworkStream;
+
+/// The key idle connections are stored under: `(host, port, scheme)`.
+pub type PoolKey = (String, Port, String);
+
+/// Configuration for a connection `Pool`.
+#[deriving(Clone)]
+pub struct Config {
+    /// How long a connection may sit idle in the pool before it is evicted.
+    ///
+    /// `None` keeps idle connections until the server closes them.
+    pub idle_timeout: Option<Duration>,
+    /// The maximum number of idle connections kept for a single key.
+    pub max_idle_per_host: uint,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            idle_timeout: Some(Duration::seconds(90)),
+            max_idle_per_host: 5,
+        }
+    }
+}
+
+/// A pool of idle connections, shared between clones of a `Client`.
+#[deriving(Clone)]
+pub struct Pool {
+    inner: Arc<Mutex<PoolImpl>>
+}
+
+struct PoolImpl {
+    idle: HashMap<PoolKey, Vec<Idle>>,
+    config: Config,
+}
+
+struct Idle {
+    stream: Box<NetworkStream + Send>,
+    since: u64,
+}
+
+fn is_expired(timeout: Option<Duration>, idle: &Idle, now: u64) -> bool {
+    match timeout {
+        Some(timeout) => {
+            let timeout = timeout.num_milliseconds() as u64 * 1_000_000;
+            now - idle.since >= timeout
+        },
+        None => false
+    }
+}
+
+impl Pool {
+    /// Create a new, empty Pool.
+    pub fn new(config: Config) -> Pool {
+        Pool {
+            inner: Arc::new(Mutex::new(PoolImpl {
+                idle: HashMap::new(),
+                config: config,
+            }))
+        }
+    }
+
+    /// Change how long idle connections are kept.
+    ///
+    /// Connections already in the pool are checked against the new value the
+    /// next time they would be handed out.
+    pub fn set_idle_timeout(&self, timeout: Option<Duration>) {
+        self.inner.lock().config.idle_timeout = timeout;
+    }
+
+    /// Take an idle connection for `key` out of the pool, if one is available.
+    ///
+    /// Connections that have been idle longer than the idle timeout are
+    /// dropped instead of being returned.
+    pub fn checkout(&self, key: &PoolKey) -> Option<PooledStream> {
+        let now = precise_time_ns();
+        let mut inner = self.inner.lock();
+        let timeout = inner.config.idle_timeout;
+        let mut found = None;
+        let mut evicted = 0u;
+        if let Some(conns) = inner.idle.get_mut(key) {
+            // prefer the most recently used connection, it's the least
+            // likely to have been closed by the server.
+            loop {
+                match conns.pop() {
+                    Some(ref idle) if is_expired(timeout, idle, now) => evicted += 1,
+                    Some(idle) => {
+                        found = Some(idle.stream);
+                        break;
+                    },
+                    None => break
+                }
+            }
+        }
+        debug!("pool checkout {}: found={}, evicted={}", key, found.is_some(), evicted);
+        found.map(|stream| PooledStream::new(self.clone(), key.clone(), stream))
+    }
+
+    /// Wrap a newly connected stream, so that it will be returned to this
+    /// pool once it is done being used.
+    pub fn wrap(&self, key: PoolKey, stream: Box<NetworkStream + Send>) -> PooledStream {
+        PooledStream::new(self.clone(), key, stream)
+    }
+
+    /// Returns the number of idle connections kept for `key`.
+    pub fn idle_count(&self, key: &PoolKey) -> uint {
+        match self.inner.lock().idle.get(key) {
+            Some(conns) => conns.len(),
+            None => 0
+        }
+    }
+
+    /// Drop all idle connections.
+    pub fn clear(&self) {
+        self.inner.lock().idle.clear();
+    }
+
+    fn checkin(&self, key: PoolKey, stream: Box<NetworkStream + Send>) {
+        let now = precise_time_ns();
+        let mut inner = self.inner.lock();
+        let max = inner.config.max_idle_per_host;
+        debug!("pool checkin {}", key);
+        let conns = match inner.idle.entry(key) {
+            Vacant(entry) => entry.set(vec![]),
+            Occupied(entry) => entry.into_mut()
+        };
+        if conns.len() >= max {
+            // drop the oldest connection to make room
+            if max == 0 {
+                return;
+            }
+            conns.remove(0);
+        }
+        conns.push(Idle {
+            stream: stream,
+            since: now,
+        });
+    }
+}
+
+/// A connection handed out by a `Pool`.
+///
+/// Once the response on this connection has been fully read, and the
+/// server allows the connection to be kept alive, the `Response` marks it
+/// as reusable. When dropped, a reusable `PooledStream` returns the
+/// underlying connection to its pool.
+///
+/// A `PooledStream` also enforces the timeouts of the request using it,
+/// re-arming the read or write timeout of the connection before every
+/// operation.
+pub struct PooledStream {
+    inner: Option<Box<NetworkStream + Send>>,
+    checkin: Option<(Pool, PoolKey)>,
+    reusable: Cell<bool>,
+    read_timeout: Option<Duration>,
+    write_timeout: Option<Duration>,
+    deadline: Option<Deadline>,
+}
+
+impl PooledStream {
+    fn new(pool: Pool, key: PoolKey, stream: Box<NetworkStream + Send>) -> PooledStream {
+        PooledStream {
+            inner: Some(stream),
+            checkin: Some((pool, key)),
+            reusable: Cell::new(false),
+            read_timeout: None,
+            write_timeout: None,
+            deadline: None,
+        }
+    }
+
+    /// Set how long a single read or write may block, and the deadline
+    /// after which every operation fails.
+    pub fn set_timeouts(&mut self, read: Option<Duration>, write: Option<Duration>,
+                        deadline: Option<Deadline>) {
+        self.read_timeout = read;
+        self.write_timeout = write;
+        self.deadline = deadline;
+    }
+
+    /// Mark whether this connection may be returned to the pool when dropped.
+    #[inline]
+    pub fn set_reusable(&self, reusable: bool) {
+        self.reusable.set(reusable);
+    }
+
+    /// Whether this connection will be returned to the pool when dropped.
+    #[inline]
+    pub fn is_reusable(&self) -> bool {
+        self.reusable.get()
+    }
+}
+
+impl Clone for PooledStream {
+    /// Clones of a `PooledStream` share the connection, but are never
+    /// returned to the pool themselves.
+    fn clone(&self) -> PooledStream {
+        PooledStream {
+            inner: self.inner.clone(),
+            checkin: None,
+            reusable: Cell::new(false),
+            read_timeout: self.read_timeout,
+            write_timeout: self.write_timeout,
+            deadline: self.deadline.clone(),
+        }
+    }
+}
+
+impl Reader for PooledStream {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        let timeout = try!(effective(self.read_timeout, self.deadline.as_ref()));
+        let inner = self.inner.as_mut().unwrap();
+        inner.set_read_timeout(timeout);
+        inner.read(buf)
+    }
+}
+
+impl Writer for PooledStream {
+    #[inline]
+    fn write(&mut self, msg: &[u8]) -> IoResult<()> {
+        let timeout = try!(effective(self.write_timeout, self.deadline.as_ref()));
+        let inner = self.inner.as_mut().unwrap();
+        inner.set_write_timeout(timeout);
+        inner.write(msg)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> IoResult<()> {
+        self.inner.as_mut().unwrap().flush()
+    }
+}
+
+impl NetworkStream for PooledStream {
+    #[inline]
+    fn peer_name(&mut self) -> IoResult<SocketAddr> {
+        self.inner.as_mut().unwrap().peer_name()
+    }
+
+    #[inline]
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) {
+        self.read_timeout = timeout;
+    }
+
+    #[inline]
+    fn set_write_timeout(&mut self, timeout: Option<Duration>) {
+        self.write_timeout = timeout;
+    }
+}
+
+impl Drop for PooledStream {
+    fn drop(&mut self) {
+        if self.reusable.get() {
+            if let (Some(stream), Some((pool, key))) = (self.inner.take(), self.checkin.take()) {
+                pool.checkin(key, stream);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::default::Default;
+    use std::io::TimedOut;
+    use std::time::Duration;
+
+    use client::timeout::Deadline;
+    use mock::MockStream;
+    use net::NetworkStream;
+    use super::{Pool, PoolKey, Config};
+
+    fn key() -> PoolKey {
+        ("127.0.0.1".into_string(), 3000, "http".into_string())
+    }
+
+    fn stream() -> Box<NetworkStream + Send> {
+        box MockStream::new() as Box<NetworkStream + Send>
+    }
+
+    #[test]
+    fn test_checkout_empty() {
+        let pool = Pool::new(Default::default());
+        assert!(pool.checkout(&key()).is_none());
+    }
+
+    #[test]
+    fn test_checkin_when_reusable() {
+        let pool = Pool::new(Default::default());
+        let conn = pool.wrap(key(), stream());
+        conn.set_reusable(true);
+        drop(conn);
+        assert_eq!(pool.idle_count(&key()), 1);
+        assert!(pool.checkout(&key()).is_some());
+        assert_eq!(pool.idle_count(&key()), 0);
+    }
+
+    #[test]
+    fn test_no_checkin_unless_reusable() {
+        let pool = Pool::new(Default::default());
+        drop(pool.wrap(key(), stream()));
+        assert_eq!(pool.idle_count(&key()), 0);
+    }
+
+    #[test]
+    fn test_clone_not_checked_in() {
+        let pool = Pool::new(Default::default());
+        let conn = pool.wrap(key(), stream());
+        let clone = conn.clone();
+        clone.set_reusable(true);
+        drop(clone);
+        assert_eq!(pool.idle_count(&key()), 0);
+    }
+
+    #[test]
+    fn test_idle_timeout_evicts() {
+        let pool = Pool::new(Config {
+            idle_timeout: Some(Duration::zero()),
+            ..Default::default()
+        });
+        let conn = pool.wrap(key(), stream());
+        conn.set_reusable(true);
+        drop(conn);
+        assert_eq!(pool.idle_count(&key()), 1);
+        assert!(pool.checkout(&key()).is_none());
+        assert_eq!(pool.idle_count(&key()), 0);
+    }
+
+    #[test]
+    fn test_deadline_fails_reads() {
+        let pool = Pool::new(Default::default());
+        let mut conn = pool.wrap(key(), stream());
+        conn.set_timeouts(None, None, Some(Deadline::after(Duration::zero())));
+        assert_eq!(conn.read(&mut [0u8, ..1]).unwrap_err().kind, TimedOut);
+        assert_eq!(conn.write(b"x").unwrap_err().kind, TimedOut);
+    }
+
+    #[test]
+    fn test_max_idle_per_host() {
+        let pool = Pool::new(Config {
+            max_idle_per_host: 1,
+            ..Default::default()
+        });
+        for _ in range(0u, 3) {
+            let conn = pool.wrap(key(), stream());
+            conn.set_reusable(true);
+        }
+        assert_eq!(pool.idle_count(&key()), 1);
+    }
+}
@@ -0,0 +1,148 @@
+//! Client Redirects
+//!
+//! The `Client` can follow `301`, `302`, `303`, `307` and `308` responses
+//! on its own, according to a `RedirectPolicy`.
+use url::{Url, UrlParser};
+
+use method::Method::{mod, Get, Head, Post};
+use status::StatusCode;
+use status::StatusCode::{MovedPermanently, Found, SeeOther, TemporaryRedirect,
+                         PermanentRedirect};
+
+use self::RedirectPolicy::{FollowNone, FollowAll, FollowSameOrigin, FollowIf};
+
+/// Behavior regarding how to handle redirects within a Client.
+pub enum RedirectPolicy {
+    /// Don't follow any redirects.
+    FollowNone,
+    /// Follow all redirects, up to the Client's maximum number of redirects.
+    FollowAll,
+    /// Follow only redirects to the same scheme, host and port.
+    FollowSameOrigin,
+    /// Follow a redirect if the contained function returns true for the
+    /// target `Url`.
+    FollowIf(fn(&Url) -> bool),
+}
+
+impl Clone for RedirectPolicy {
+    fn clone(&self) -> RedirectPolicy {
+        match *self {
+            FollowNone => FollowNone,
+            FollowAll => FollowAll,
+            FollowSameOrigin => FollowSameOrigin,
+            FollowIf(f) => FollowIf(f),
+        }
+    }
+}
+
+impl RedirectPolicy {
+    /// Whether this policy allows following a redirect from `from` to `to`.
+    pub fn allows(&self, from: &Url, to: &Url) -> bool {
+        match *self {
+            FollowNone => false,
+            FollowAll => true,
+            FollowSameOrigin => same_origin(from, to),
+            FollowIf(f) => f(to),
+        }
+    }
+}
+
+/// Whether two Urls share the same scheme, host and port.
+pub fn same_origin(a: &Url, b: &Url) -> bool {
+    a.scheme == b.scheme &&
+        a.host() == b.host() &&
+        a.port_or_default() == b.port_or_default()
+}
+
+/// Resolve the value of a `Location` header against the Url of the
+/// request it answered.
+pub fn resolve_location(base: &Url, location: &str) -> Option<Url> {
+    match UrlParser::new().base_url(base).parse(location) {
+        Ok(url) => Some(url),
+        Err(_e) => {
+            debug!("invalid Location {}: {}", location, _e);
+            None
+        }
+    }
+}
+
+/// The method to use for the request following a redirect with `status`.
+///
+/// Returns `None` if `status` isn't a redirect that can be followed.
+///
+/// A `303 See Other` is always followed with a `GET` (or `HEAD`), and
+/// `307`/`308` keep the original method. Like browsers do, a `POST`
+/// answered with `301` or `302` is followed with a `GET`.
+pub fn redirect_method(status: StatusCode, method: &Method) -> Option<Method> {
+    match status {
+        MovedPermanently | Found => Some(match *method {
+            Post => Get,
+            ref other => other.clone()
+        }),
+        SeeOther => Some(match *method {
+            Head => Head,
+            _ => Get
+        }),
+        TemporaryRedirect | PermanentRedirect => Some(method.clone()),
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use url::Url;
+
+    use method::Method::{Get, Head, Post, Put};
+    use status::StatusCode::{Ok, MovedPermanently, Found, SeeOther,
+                             TemporaryRedirect, PermanentRedirect};
+    use super::{redirect_method, resolve_location, same_origin};
+    use super::RedirectPolicy::{FollowNone, FollowAll, FollowSameOrigin, FollowIf};
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_redirect_method() {
+        assert_eq!(redirect_method(MovedPermanently, &Put), Some(Put));
+        assert_eq!(redirect_method(Found, &Post), Some(Get));
+        assert_eq!(redirect_method(SeeOther, &Put), Some(Get));
+        assert_eq!(redirect_method(SeeOther, &Head), Some(Head));
+        assert_eq!(redirect_method(TemporaryRedirect, &Post), Some(Post));
+        assert_eq!(redirect_method(PermanentRedirect, &Put), Some(Put));
+        assert_eq!(redirect_method(Ok, &Get), None);
+    }
+
+    #[test]
+    fn test_resolve_location() {
+        let base = url("http://example.domain/a/b?c");
+        assert_eq!(resolve_location(&base, "/x"), Some(url("http://example.domain/x")));
+        assert_eq!(resolve_location(&base, "y"), Some(url("http://example.domain/a/y")));
+        assert_eq!(resolve_location(&base, "https://other.domain/"),
+                   Some(url("https://other.domain/")));
+    }
+
+    #[test]
+    fn test_same_origin() {
+        assert!(same_origin(&url("http://a.domain/x"), &url("http://a.domain:80/y")));
+        assert!(!same_origin(&url("http://a.domain/"), &url("https://a.domain/")));
+        assert!(!same_origin(&url("http://a.domain/"), &url("http://b.domain/")));
+        assert!(!same_origin(&url("http://a.domain/"), &url("http://a.domain:8080/")));
+    }
+
+    #[test]
+    fn test_policy_allows() {
+        fn only_https(url: &Url) -> bool {
+            url.scheme[] == "https"
+        }
+
+        let from = url("http://a.domain/");
+        let to = url("https://b.domain/");
+        assert!(!FollowNone.allows(&from, &from));
+        assert!(FollowAll.allows(&from, &to));
+        assert!(FollowSameOrigin.allows(&from, &url("http://a.domain/other")));
+        assert!(!FollowSameOrigin.allows(&from, &to));
+        assert!(FollowIf(only_https).allows(&from, &to));
+        assert!(!FollowIf(only_https).allows(&to, &from));
+    }
+}
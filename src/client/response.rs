@@ -1,18 +1,34 @@
 //! Client Responses
+use std::any::AnyRefExt;
+use std::borrow::Borrowed;
 use std::num::FromPrimitive;
-use std::io::{BufferedReader, IoResult};
+use std::io::{BufferedReader, IoError, IoResult, EndOfFile, InvalidInput, MemReader,
+              NotConnected, standard_error};
+use std::io::net::ip::SocketAddr;
+use std::mem::replace;
+use std::sync::{Arc, Mutex};
 
+use url::Url;
+
+use client::pool::PooledStream;
 use header;
-use header::common::{ContentLength, TransferEncoding};
-use header::common::transfer_encoding::Encoding::Chunked;
+use header::common::{ContentEncoding, ContentLength, TransferEncoding};
+use header::common::transfer_encoding::Encoding::{mod, Gzip, Deflate};
+use method::Method::{mod, Connect};
 use net::{NetworkStream, HttpStream};
-use http::{read_status_line, HttpReader, RawStatus};
-use http::HttpReader::{SizedReader, ChunkedReader, EofReader};
+use http::{read_status_line, response_reader, should_keep_alive, HttpReader, RawStatus};
+use http::HttpReader::{SizedReader, EofReader, EmptyReader};
+use progress::{Meter, Progress, Throttle};
 use status;
 use version;
 use HttpResult;
 use HttpError::HttpStatusError;
 
+use flate2::reader::{GzDecoder, ZlibDecoder};
+
+use self::Decoder::{Identity, Pending, GzipDecoder, DeflateDecoder, RawDeflateDecoder, Decoded,
+                    Failed};
+
 /// A response for a client request to a remote server.
 pub struct Response<S = HttpStream> {
     /// The status from the server.
@@ -21,75 +37,435 @@ pub struct Response<S = HttpStream> {
     pub headers: header::Headers,
     /// The HTTP version of this response from the server.
     pub version: version::HttpVersion,
+    /// The Urls requested to get this response, in order.
+    ///
+    /// When a `Client` followed redirects, the last Url is the one this
+    /// response came from. Empty for responses not sent through a
+    /// `RequestBuilder`.
+    pub history: Vec<Url>,
     status_raw: RawStatus,
-    body: HttpReader<BufferedReader<Box<NetworkStream + Send>>>,
+    body: Decoder,
+    trailers: Arc<Mutex<Option<header::Headers>>>,
+}
+
+/// The body of a response, straight from the connection.
+///
+/// Once it has been read to the end, a pooled connection is marked as
+/// reusable.
+struct Body {
+    reader: Meter<HttpReader<BufferedReader<Box<NetworkStream + Send>>>>,
+    keep_alive: bool,
+    // shared with the Response, since a decoder owns the Body
+    trailers: Arc<Mutex<Option<header::Headers>>>,
+}
+
+impl Body {
+    /// Marks a pooled connection as reusable, once the body has been read.
+    fn release(&self) {
+        if let Some(trailers) = self.reader.get_ref().trailers() {
+            *self.trailers.lock() = Some(trailers.clone());
+        }
+        if self.keep_alive {
+            let stream: &NetworkStream = &**self.reader.get_ref().get_ref().get_ref();
+            if let Some(pooled) = stream.downcast_ref::<PooledStream>() {
+                debug!("body finished, connection can be reused");
+                pooled.set_reusable(true);
+            }
+        }
+    }
+
+    fn into_buffered(self) -> BufferedReader<Box<NetworkStream + Send>> {
+        self.reader.into_inner().unwrap()
+    }
+}
+
+impl Reader for Body {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        let result = self.reader.read(buf);
+        match result {
+            Err(ref e) if e.kind == EndOfFile => self.release(),
+            // a decoder may never read up to the end
+            Ok(_) => if let SizedReader(_, 0) = *self.reader.get_ref() { self.release() },
+            _ => ()
+        }
+        result
+    }
+}
+
+// The first bytes of a body, read to tell whether it's encoded at all,
+// followed by the rest of it.
+struct Prefixed {
+    prefix: MemReader,
+    body: Body,
+}
+
+impl Reader for Prefixed {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        match self.prefix.read(buf) {
+            Err(ref e) if e.kind == EndOfFile => self.body.read(buf),
+            result => result
+        }
+    }
+}
+
+enum Decoder {
+    Identity(Body),
+    // Decoders are only set up by the first read, so that an empty body
+    // doesn't fail for lack of a gzip header.
+    Pending(Body, Encoding),
+    GzipDecoder(GzDecoder<Prefixed>),
+    DeflateDecoder(ZlibDecoder<Prefixed>),
+    // Some servers send `deflate` data without its zlib wrapper.
+    RawDeflateDecoder(::flate2::reader::DeflateDecoder<Prefixed>),
+    // The decoder reached the end of the encoded data, and what was left
+    // of the body has been read.
+    Decoded(Body),
+    // Setting up a decoder failed, and the body is lost.
+    Failed,
+}
+
+static GZIP_MAGIC: &'static [u8] = &[0x1f, 0x8b];
+
+// Whether `prefix` is a zlib header: the deflate method, with a window of
+// at most 32K, and a check of the two bytes.
+fn is_zlib_header(prefix: &[u8]) -> bool {
+    prefix.len() == 2 && prefix[0] & 0x0F == 8 && prefix[0] >> 4 <= 7 &&
+        ((prefix[0] as u16) << 8 | prefix[1] as u16) % 31 == 0
+}
+
+fn decode_error(detail: Option<String>) -> IoError {
+    IoError {
+        kind: InvalidInput,
+        desc: "The body could not be decoded",
+        detail: detail
+    }
 }
 
 impl Response {
 
-    /// Creates a new response from a server.
-    pub fn new(stream: Box<NetworkStream + Send>) -> HttpResult<Response> {
+    /// Creates a new response from a server, to a request with `method`.
+    ///
+    /// Interim `1xx` responses are skipped, other than a `101 Switching
+    /// Protocols`, and the final response is returned.
+    pub fn new(stream: Box<NetworkStream + Send>, method: &Method) -> HttpResult<Response> {
         let mut stream = BufferedReader::new(stream);
-        let (version, raw_status) = try!(read_status_line(&mut stream));
+        loop {
+            let (version, raw_status) = try!(read_status_line(&mut stream));
+            let headers = try!(header::Headers::from_raw(&mut stream));
+            // A `100 Continue` can arrive after the client stopped waiting
+            // for it, and is followed by the final response, as are other
+            // interim responses such as `102 Processing`.
+            if raw_status.0 / 100 == 1 && raw_status.0 != 101 {
+                debug!("skipping interim {} response", raw_status.0);
+                continue;
+            }
+            return Response::from_head(stream, method, version, raw_status, headers, true);
+        }
+    }
+
+    /// Creates a response that was never received from a server, such as
+    /// one an `Interceptor` answers a request with.
+    pub fn synthesized(status: status::StatusCode, headers: header::Headers,
+                       body: Vec<u8>) -> Response {
+        let raw_status = RawStatus(status as u16,
+                                   Borrowed(status.canonical_reason().unwrap_or("")));
+        Response::buffered(version::HttpVersion::Http11, raw_status, headers, body, None).unwrap()
+    }
+
+    /// Creates a response whose body has already been read into `body`,
+    /// along with its `trailers`, if it was chunked.
+    ///
+    /// The headers are kept as they were received, and the body is read
+    /// until its end regardless of them.
+    pub fn buffered(version: version::HttpVersion, raw_status: RawStatus,
+                    headers: header::Headers, body: Vec<u8>,
+                    trailers: Option<header::Headers>) -> HttpResult<Response> {
         let status = match FromPrimitive::from_u16(raw_status.0) {
             Some(status) => status,
             None => return Err(HttpStatusError)
         };
-        debug!("{} {}", version, status);
+        let stream = box SyntheticStream(MemReader::new(body)) as Box<NetworkStream + Send>;
+        let trailers = Arc::new(Mutex::new(trailers));
+        Ok(Response {
+            status: status,
+            version: version,
+            headers: headers,
+            history: vec![],
+            body: Identity(Body {
+                reader: Meter::new(EofReader(BufferedReader::new(stream)), None),
+                keep_alive: false,
+                trailers: trailers.clone(),
+            }),
+            status_raw: raw_status,
+            trailers: trailers,
+        })
+    }
+
+    /// Creates a response to a request with `method`, whose status line and
+    /// headers have already been read from `stream`.
+    ///
+    /// Unless `reusable` is true, the connection is never returned to a
+    /// pool, such as when the request it answers wasn't sent completely.
+    pub fn with_head(stream: Box<NetworkStream + Send>, method: &Method,
+                     version: version::HttpVersion, raw_status: RawStatus,
+                     headers: header::Headers, reusable: bool) -> HttpResult<Response> {
+        Response::from_head(BufferedReader::new(stream), method, version, raw_status, headers,
+                            reusable)
+    }
 
-        let headers = try!(header::Headers::from_raw(&mut stream));
+    fn from_head(stream: BufferedReader<Box<NetworkStream + Send>>, method: &Method,
+                 version: version::HttpVersion, raw_status: RawStatus,
+                 headers: header::Headers, reusable: bool) -> HttpResult<Response> {
+        let status = match FromPrimitive::from_u16(raw_status.0) {
+            Some(status) => status,
+            None => return Err(HttpStatusError)
+        };
+        debug!("{} {}", version, status);
         debug!("{}", headers);
 
-        let body = if headers.has::<TransferEncoding>() {
-            match headers.get::<TransferEncoding>() {
-                Some(&TransferEncoding(ref codings)) => {
-                    if codings.len() > 1 {
-                        debug!("TODO: #2 handle other codings: {}", codings);
-                    };
-
-                    if codings.contains(&Chunked) {
-                        ChunkedReader(stream, None)
-                    } else {
-                        debug!("not chuncked. read till eof");
-                        EofReader(stream)
-                    }
-                }
-                None => unreachable!()
+        if let Some(&TransferEncoding(ref codings)) = headers.get::<TransferEncoding>() {
+            if codings.len() > 1 {
+                debug!("TODO: #2 handle other codings: {}", codings);
             }
-        } else if headers.has::<ContentLength>() {
-            match headers.get::<ContentLength>() {
-                Some(&ContentLength(len)) => SizedReader(stream, len),
-                None => unreachable!()
-            }
-        } else {
-            debug!("neither Transfer-Encoding nor Content-Length");
-            EofReader(stream)
+        }
+        let reader = try!(response_reader(stream, method, raw_status.0, &headers));
+
+        // Only bodies with a known end leave the connection ready for the
+        // next message. After a `101` or a `CONNECT`, it isn't HTTP anymore.
+        let keep_alive = match reader {
+            EofReader(..) => false,
+            _ if raw_status.0 == 101 || *method == Connect => false,
+            _ => reusable && should_keep_alive(version, &headers)
         };
+        let trailers = Arc::new(Mutex::new(None));
+        let len = headers.get::<ContentLength>().map(|len| len.len() as u64);
+        let body = Body {
+            reader: Meter::new(reader, len),
+            keep_alive: keep_alive,
+            trailers: trailers.clone(),
+        };
+        // there's nothing to read before the connection can be reused
+        if let EmptyReader(..) = *body.reader.get_ref() {
+            body.release();
+        }
 
         Ok(Response {
             status: status,
             version: version,
             headers: headers,
-            body: body,
+            history: vec![],
+            body: Identity(body),
             status_raw: raw_status,
+            trailers: trailers,
         })
     }
 
+    /// Decode the body according to the `Content-Encoding` header, if it is
+    /// `gzip` or `deflate`.
+    ///
+    /// The headers are left as they are, so `Content-Length` is the length
+    /// of the encoded body. Reading a body that turns out not to be encoded
+    /// properly fails with an IoError.
+    pub fn decompress(&mut self) {
+        let encoding = match self.headers.get::<ContentEncoding>() {
+            Some(&ContentEncoding(ref codings)) if codings.len() == 1 => {
+                match codings[0] {
+                    Gzip => Gzip,
+                    Deflate => Deflate,
+                    _ => return
+                }
+            },
+            Some(_codings) => {
+                debug!("not decoding Content-Encoding: {}", _codings);
+                return;
+            },
+            None => return
+        };
+        self.body = match replace(&mut self.body, Failed) {
+            Identity(body) => Pending(body, encoding),
+            other => other
+        };
+    }
+
+    /// Whether the body is decoded as it is read, after `decompress`.
+    pub fn is_decompressing(&self) -> bool {
+        match self.body {
+            Identity(..) => false,
+            _ => true
+        }
+    }
+
+    /// Report the progress of reading the body to `progress`, with its
+    /// `Content-Length` as the total.
+    ///
+    /// The bytes are counted as they were received, before any decoding.
+    /// This has no effect once the body has started to be decoded.
+    pub fn set_progress<P: Progress>(&mut self, progress: P) {
+        match self.body {
+            Identity(ref mut body) | Pending(ref mut body, _) => {
+                body.reader.set_progress(progress)
+            },
+            _ => debug!("too late to report the progress of the body")
+        }
+    }
+
+    /// Cap the rate at which the body is read with `throttle`.
+    ///
+    /// This has no effect once the body has started to be decoded.
+    pub fn set_throttle(&mut self, throttle: Throttle) {
+        match self.body {
+            Identity(ref mut body) | Pending(ref mut body, _) => {
+                body.reader.set_throttle(throttle)
+            },
+            _ => debug!("too late to throttle the body")
+        }
+    }
+
     /// Get the raw status code and reason.
     pub fn status_raw(&self) -> &RawStatus {
         &self.status_raw
     }
 
+    /// The trailers sent after a chunked body, once the body has been read
+    /// to the end.
+    pub fn trailers(&self) -> Option<header::Headers> {
+        self.trailers.lock().clone()
+    }
+
     /// Consumes the Request to return the NetworkStream underneath.
-    pub fn into_inner(self) -> Box<NetworkStream + Send> {
-        self.body.unwrap().into_inner()
+    ///
+    /// Fails if the body couldn't be decoded, which leaves the stream in an
+    /// unknown state.
+    pub fn into_inner(self) -> IoResult<Box<NetworkStream + Send>> {
+        self.into_buffered().map(|stream| stream.into_inner())
+    }
+
+    /// Consumes the Response to return the NetworkStream underneath, along
+    /// with what was already read from it, but not from the body.
+    ///
+    /// After a `101 Switching Protocols`, this is where the new protocol
+    /// starts. Fails like `into_inner`.
+    pub fn into_buffered(self) -> IoResult<BufferedReader<Box<NetworkStream + Send>>> {
+        match self.body {
+            Identity(body) | Pending(body, _) | Decoded(body) => Ok(body.into_buffered()),
+            GzipDecoder(decoder) => Ok(decoder.into_inner().body.into_buffered()),
+            DeflateDecoder(decoder) => Ok(decoder.into_inner().body.into_buffered()),
+            RawDeflateDecoder(decoder) => Ok(decoder.into_inner().body.into_buffered()),
+            Failed => Err(decode_error(None))
+        }
+    }
+
+    fn start_decoding(&mut self) -> IoResult<()> {
+        let (mut body, encoding) = match replace(&mut self.body, Failed) {
+            Pending(body, encoding) => (body, encoding),
+            other => {
+                self.body = other;
+                return Ok(());
+            }
+        };
+        // a look at the first bytes: an empty body isn't encoded at all
+        let mut prefix = vec![];
+        let mut buf = [0u8, ..2];
+        while prefix.len() < buf.len() {
+            let len = buf.len() - prefix.len();
+            match body.read(buf.slice_to_mut(len)) {
+                Ok(n) => prefix.push_all(buf[..n]),
+                Err(ref e) if e.kind == EndOfFile => break,
+                Err(e) => return Err(e)
+            }
+        }
+        if prefix.is_empty() {
+            self.body = Identity(body);
+            return Ok(());
+        }
+        if encoding == Gzip && prefix[] != GZIP_MAGIC {
+            return Err(decode_error(Some("not in the gzip format".into_string())));
+        }
+        let zlib = is_zlib_header(prefix[]);
+        let body = Prefixed { prefix: MemReader::new(prefix), body: body };
+        self.body = match encoding {
+            Gzip => GzipDecoder(try!(GzDecoder::new(body).map_err(|e| decode_error(e.detail)))),
+            _ if zlib => DeflateDecoder(ZlibDecoder::new(body)),
+            _ => RawDeflateDecoder(::flate2::reader::DeflateDecoder::new(body))
+        };
+        Ok(())
+    }
+}
+
+impl Response {
+    // A decoder stops at the end of the encoded data, which may leave the
+    // end of a chunked body, and its trailers, unread. The body is read to
+    // its end, so that the connection can be reused.
+    fn finish_decoding(&mut self) -> IoResult<()> {
+        let mut body = match replace(&mut self.body, Failed) {
+            GzipDecoder(decoder) => decoder.into_inner(),
+            DeflateDecoder(decoder) => decoder.into_inner(),
+            RawDeflateDecoder(decoder) => decoder.into_inner(),
+            other => {
+                self.body = other;
+                return Ok(());
+            }
+        };
+        let mut buf = [0u8, ..64];
+        loop {
+            match body.read(&mut buf) {
+                Ok(_) => (),
+                Err(ref e) if e.kind == EndOfFile => break,
+                Err(e) => return Err(e)
+            }
+        }
+        self.body = Decoded(body.body);
+        Ok(())
     }
 }
 
 impl Reader for Response {
     #[inline]
     fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
-        self.body.read(buf)
+        if let Pending(..) = self.body {
+            try!(self.start_decoding());
+        }
+        let result = match self.body {
+            Identity(ref mut body) | Decoded(ref mut body) => return body.read(buf),
+            GzipDecoder(ref mut decoder) => decoder.read(buf),
+            DeflateDecoder(ref mut decoder) => decoder.read(buf),
+            RawDeflateDecoder(ref mut decoder) => decoder.read(buf),
+            Pending(..) => unreachable!(),
+            Failed => return Err(decode_error(None))
+        };
+        if let Err(ref e) = result {
+            if e.kind == EndOfFile {
+                try!(self.finish_decoding());
+            }
+        }
+        result
+    }
+}
+
+// The stream of a synthesized Response.
+struct SyntheticStream(MemReader);
+
+impl Clone for SyntheticStream {
+    fn clone(&self) -> SyntheticStream {
+        SyntheticStream(MemReader::new(self.0.get_ref().to_vec()))
+    }
+}
+
+impl Reader for SyntheticStream {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        self.0.read(buf)
+    }
+}
+
+impl Writer for SyntheticStream {
+    fn write(&mut self, _msg: &[u8]) -> IoResult<()> {
+        Err(standard_error(NotConnected))
+    }
+}
+
+impl NetworkStream for SyntheticStream {
+    fn peer_name(&mut self) -> IoResult<SocketAddr> {
+        Err(standard_error(NotConnected))
     }
 }
 
@@ -97,17 +473,24 @@ impl Reader for Response {
 mod tests {
     use std::borrow::Borrowed;
     use std::boxed::BoxAny;
-    use std::io::BufferedReader;
+    use std::default::Default;
+    use std::io::{BufferedReader, InvalidInput};
+    use std::sync::{Arc, Mutex};
 
+    use client::pool::Pool;
     use header::Headers;
+    use http::{RawStatus, is_incomplete};
     use http::HttpReader::EofReader;
-    use http::RawStatus;
+    use method::Method::{Get, Head};
     use mock::MockStream;
     use net::NetworkStream;
+    use progress::Meter;
     use status;
     use version;
 
-    use super::Response;
+    use super::{Response, Body};
+    use super::Decoder::Identity;
+    use HttpError::HttpHeaderError;
 
 
     #[test]
@@ -116,12 +499,251 @@ mod tests {
             status: status::StatusCode::Ok,
             headers: Headers::new(),
             version: version::HttpVersion::Http11,
-            body: EofReader(BufferedReader::new(box MockStream::new() as Box<NetworkStream + Send>)),
-            status_raw: RawStatus(200, Borrowed("OK"))
+            history: vec![],
+            body: Identity(Body {
+                reader: Meter::new(EofReader(BufferedReader::new(box MockStream::new() as
+                                                                 Box<NetworkStream + Send>)),
+                                   None),
+                keep_alive: false,
+                trailers: Arc::new(Mutex::new(None)),
+            }),
+            status_raw: RawStatus(200, Borrowed("OK")),
+            trailers: Arc::new(Mutex::new(None)),
         };
 
-        let b = res.into_inner().downcast::<MockStream>().unwrap();
+        let b = res.into_inner().unwrap().downcast::<MockStream>().unwrap();
         assert_eq!(b, box MockStream::new());
 
     }
+
+    fn pooled(pool: &Pool, input: &[u8]) -> Box<NetworkStream + Send> {
+        let key = ("example.domain".into_string(), 80, "http".into_string());
+        box pool.wrap(key, box MockStream::with_input(input) as Box<NetworkStream + Send>)
+            as Box<NetworkStream + Send>
+    }
+
+    #[test]
+    fn test_keep_alive_returns_to_pool() {
+        let pool = Pool::new(Default::default());
+        let key = ("example.domain".into_string(), 80, "http".into_string());
+        {
+            let mut res = Response::new(pooled(&pool, b"\
+                HTTP/1.1 200 OK\r\n\
+                Content-Length: 5\r\n\
+                \r\n\
+                hello\
+            "), &Get).unwrap();
+            assert_eq!(res.read_to_string(), Ok("hello".into_string()));
+        }
+        assert_eq!(pool.idle_count(&key), 1);
+    }
+
+    #[test]
+    fn test_unread_body_not_returned_to_pool() {
+        let pool = Pool::new(Default::default());
+        let key = ("example.domain".into_string(), 80, "http".into_string());
+        {
+            let _res = Response::new(pooled(&pool, b"\
+                HTTP/1.1 200 OK\r\n\
+                Content-Length: 5\r\n\
+                \r\n\
+                hello\
+            "), &Get).unwrap();
+        }
+        assert_eq!(pool.idle_count(&key), 0);
+    }
+
+    #[test]
+    fn test_connection_close_not_returned_to_pool() {
+        let pool = Pool::new(Default::default());
+        let key = ("example.domain".into_string(), 80, "http".into_string());
+        {
+            let mut res = Response::new(pooled(&pool, b"\
+                HTTP/1.1 200 OK\r\n\
+                Content-Length: 5\r\n\
+                Connection: close\r\n\
+                \r\n\
+                hello\
+            "), &Get).unwrap();
+            assert_eq!(res.read_to_string(), Ok("hello".into_string()));
+        }
+        assert_eq!(pool.idle_count(&key), 0);
+    }
+
+    #[test]
+    fn test_chunked_trailers_returns_to_pool() {
+        let pool = Pool::new(Default::default());
+        let key = ("example.domain".into_string(), 80, "http".into_string());
+        {
+            let mut res = Response::new(pooled(&pool, b"\
+                HTTP/1.1 200 OK\r\n\
+                Transfer-Encoding: chunked\r\n\
+                Trailer: Checksum\r\n\
+                \r\n\
+                5\r\nhello\r\n\
+                0\r\n\
+                Checksum: abc\r\n\
+                \r\n\
+            "), &Get).unwrap();
+            assert!(res.trailers().is_none());
+            assert_eq!(res.read_to_string(), Ok("hello".into_string()));
+            let trailers = res.trailers().unwrap();
+            assert_eq!(trailers.get_raw("Checksum").unwrap()[0], b"abc".to_vec());
+        }
+        assert_eq!(pool.idle_count(&key), 1);
+    }
+
+    #[test]
+    fn test_head_returns_to_pool() {
+        let pool = Pool::new(Default::default());
+        let key = ("example.domain".into_string(), 80, "http".into_string());
+        {
+            let mut res = Response::new(pooled(&pool, b"\
+                HTTP/1.1 200 OK\r\n\
+                Content-Length: 5\r\n\
+                \r\n\
+            "), &Head).unwrap();
+            assert_eq!(res.read_to_end(), Ok(vec![]));
+        }
+        assert_eq!(pool.idle_count(&key), 1);
+    }
+
+    #[test]
+    fn test_no_content_needs_no_read() {
+        let pool = Pool::new(Default::default());
+        let key = ("example.domain".into_string(), 80, "http".into_string());
+        {
+            let res = Response::new(pooled(&pool, b"\
+                HTTP/1.1 102 Processing\r\n\
+                \r\n\
+                HTTP/1.1 204 No Content\r\n\
+                \r\n\
+            "), &Get).unwrap();
+            assert_eq!(res.status, status::StatusCode::NoContent);
+        }
+        assert_eq!(pool.idle_count(&key), 1);
+    }
+
+    #[test]
+    fn test_incomplete_body() {
+        let mut res = Response::new(box MockStream::with_input(b"\
+            HTTP/1.1 200 OK\r\n\
+            Content-Length: 10\r\n\
+            \r\n\
+            hello\
+        ") as Box<NetworkStream + Send>, &Get).unwrap();
+        assert!(is_incomplete(&res.read_to_end().unwrap_err()));
+    }
+
+    #[test]
+    fn test_conflicting_length() {
+        let res = Response::new(box MockStream::with_input(b"\
+            HTTP/1.1 200 OK\r\n\
+            Content-Length: 5\r\n\
+            Transfer-Encoding: chunked\r\n\
+            \r\n\
+            5\r\nhello\r\n0\r\n\r\n\
+        ") as Box<NetworkStream + Send>, &Get);
+        assert_eq!(res.err(), Some(HttpHeaderError));
+    }
+
+    #[test]
+    fn test_synthesized() {
+        let mut res = Response::synthesized(status::StatusCode::NotFound, Headers::new(),
+                                            b"missing".to_vec());
+        assert_eq!(res.status, status::StatusCode::NotFound);
+        assert_eq!(res.status_raw(), &RawStatus(404, Borrowed("Not Found")));
+        assert_eq!(res.read_to_string(), Ok("missing".into_string()));
+    }
+
+    #[test]
+    fn test_gzip() {
+        let mut res = Response::new(box MockStream::with_input(b"\
+            HTTP/1.1 200 OK\r\n\
+            Content-Encoding: gzip\r\n\
+            Content-Length: 25\r\n\
+            \r\n\
+            \x1f\x8b\x08\x00\x00\x00\x00\x00\x02\x03\xcb\x48\xcd\xc9\xc9\x07\x00\
+            \x86\xa6\x10\x36\x05\x00\x00\x00\
+        ") as Box<NetworkStream + Send>, &Get).unwrap();
+        res.decompress();
+        assert_eq!(res.read_to_string(), Ok("hello".into_string()));
+    }
+
+    #[test]
+    fn test_gzip_chunked_trailers() {
+        let mut res = Response::new(box MockStream::with_input(b"\
+            HTTP/1.1 200 OK\r\n\
+            Content-Encoding: gzip\r\n\
+            Transfer-Encoding: chunked\r\n\
+            Trailer: Checksum\r\n\
+            \r\n\
+            19\r\n\
+            \x1f\x8b\x08\x00\x00\x00\x00\x00\x02\x03\xcb\x48\xcd\xc9\xc9\x07\x00\
+            \x86\xa6\x10\x36\x05\x00\x00\x00\r\n\
+            0\r\n\
+            Checksum: abc\r\n\
+            \r\n\
+        ") as Box<NetworkStream + Send>, &Get).unwrap();
+        res.decompress();
+        assert_eq!(res.read_to_string(), Ok("hello".into_string()));
+        let trailers = res.trailers().unwrap();
+        assert_eq!(trailers.get_raw("Checksum").unwrap()[0], b"abc".to_vec());
+        assert!(res.is_decompressing());
+    }
+
+    #[test]
+    fn test_deflate() {
+        let mut res = Response::new(box MockStream::with_input(b"\
+            HTTP/1.1 200 OK\r\n\
+            Content-Encoding: deflate\r\n\
+            Content-Length: 13\r\n\
+            \r\n\
+            \x78\x9c\xcb\x48\xcd\xc9\xc9\x07\x00\x06\x2c\x02\x15\
+        ") as Box<NetworkStream + Send>, &Get).unwrap();
+        res.decompress();
+        assert_eq!(res.read_to_string(), Ok("hello".into_string()));
+    }
+
+    #[test]
+    fn test_raw_deflate() {
+        let mut res = Response::new(box MockStream::with_input(b"\
+            HTTP/1.1 200 OK\r\n\
+            Content-Encoding: deflate\r\n\
+            Content-Length: 7\r\n\
+            \r\n\
+            \xcb\x48\xcd\xc9\xc9\x07\x00\
+        ") as Box<NetworkStream + Send>, &Get).unwrap();
+        res.decompress();
+        assert_eq!(res.read_to_string(), Ok("hello".into_string()));
+    }
+
+    #[test]
+    fn test_gzip_empty_body() {
+        let mut res = Response::new(box MockStream::with_input(b"\
+            HTTP/1.1 200 OK\r\n\
+            Content-Encoding: gzip\r\n\
+            Content-Length: 0\r\n\
+            \r\n\
+        ") as Box<NetworkStream + Send>, &Get).unwrap();
+        res.decompress();
+        assert_eq!(res.read_to_end(), Ok(vec![]));
+        assert!(res.into_inner().is_ok());
+    }
+
+    #[test]
+    fn test_invalid_gzip() {
+        let mut res = Response::new(box MockStream::with_input(b"\
+            HTTP/1.1 200 OK\r\n\
+            Content-Encoding: gzip\r\n\
+            Content-Length: 5\r\n\
+            \r\n\
+            hello\
+        ") as Box<NetworkStream + Send>, &Get).unwrap();
+        res.decompress();
+        assert_eq!(res.read_to_end().unwrap_err().kind, InvalidInput);
+        // reading again doesn't pass for the end of the body
+        assert_eq!(res.read_to_end().unwrap_err().kind, InvalidInput);
+        assert_eq!(res.into_inner().err().map(|e| e.kind), Some(InvalidInput));
+    }
 }
@@ -0,0 +1,194 @@
+//! Client Retries
+//!
+//! A request can fail for reasons that have nothing to do with the request
+//! itself: a pooled connection the server already closed, a connection
+//! reset, or a `503 Service Unavailable` asking to come back later. A
+//! `RetryPolicy` decides when such a request is sent again, and how long to
+//! wait before doing so.
+//!
+//! Only requests with an idempotent method are retried, since a request
+//! that failed may still have been processed by the server.
+use std::cmp::min;
+use std::io::{ConnectionRefused, ConnectionReset, ConnectionAborted, BrokenPipe, EndOfFile};
+use std::rand::random;
+use std::time::Duration;
+
+use time::now_utc;
+
+use header::common::RetryAfter;
+use http::is_incomplete;
+use header::common::retry_after::{Delay, DateTime};
+use method::Method;
+use status::StatusCode::ServiceUnavailable;
+use client::Response;
+use HttpError::{mod, HttpIoError};
+
+/// When to send a failed request again.
+#[deriving(Clone, PartialEq, Show)]
+pub struct RetryPolicy {
+    max_attempts: uint,
+    base_delay: Duration,
+    max_delay: Duration,
+    max_retry_after: Duration,
+}
+
+impl RetryPolicy {
+    /// A policy that makes up to 3 attempts, backing off from 100
+    /// milliseconds up to 10 seconds, and waits for a `Retry-After` of up
+    /// to a minute.
+    pub fn new() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::milliseconds(100),
+            max_delay: Duration::seconds(10),
+            max_retry_after: Duration::seconds(60),
+        }
+    }
+
+    /// A policy that never retries.
+    pub fn never() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 1,
+            .. RetryPolicy::new()
+        }
+    }
+
+    /// Set how many times a request is sent at most, including the first
+    /// attempt.
+    pub fn set_max_attempts(&mut self, attempts: uint) {
+        self.max_attempts = attempts;
+    }
+
+    /// Set the delay before the first retry, and the most the delay may
+    /// grow to. The delay doubles with every retry.
+    pub fn set_backoff(&mut self, base: Duration, max: Duration) {
+        self.base_delay = base;
+        self.max_delay = max;
+    }
+
+    /// Set the longest `Retry-After` that will be waited for. A `503`
+    /// asking for a longer wait is returned as it is.
+    pub fn set_max_retry_after(&mut self, max: Duration) {
+        self.max_retry_after = max;
+    }
+
+    /// How many times a request is sent at most.
+    #[inline]
+    pub fn max_attempts(&self) -> uint { self.max_attempts }
+
+    /// The delay before the next attempt, after `attempts` failed ones.
+    ///
+    /// The delay grows exponentially, and is jittered to somewhere between
+    /// half of it and all of it, so that clients failing together don't
+    /// retry together.
+    pub fn backoff(&self, attempts: uint) -> Duration {
+        let base = self.base_delay.num_milliseconds();
+        let max = self.max_delay.num_milliseconds();
+        let exp = min(if attempts > 0 { attempts - 1 } else { 0 }, 32);
+        let delay = min(base.checked_mul(1i64 << exp).unwrap_or(max), max);
+        let jitter = (delay as f64 / 2.0 * random::<f64>()) as i64;
+        Duration::milliseconds(delay - jitter)
+    }
+
+    /// How long to wait before retrying a `method` request that failed
+    /// with `err` after `attempts` attempts, or `None` if it shouldn't be
+    /// retried.
+    pub fn retry_error(&self, method: &Method, err: &HttpError,
+                       attempts: uint) -> Option<Duration> {
+        if attempts >= self.max_attempts || !method.idempotent() || !is_transient(err) {
+            return None;
+        }
+        Some(self.backoff(attempts))
+    }
+
+    /// How long to wait before retrying a `method` request answered with
+    /// `res` after `attempts` attempts, or `None` if the response should be
+    /// returned.
+    ///
+    /// Only a `503 Service Unavailable` with a `Retry-After` is retried.
+    pub fn retry_response(&self, method: &Method, res: &Response,
+                          attempts: uint) -> Option<Duration> {
+        if attempts >= self.max_attempts || !method.idempotent() ||
+                res.status != ServiceUnavailable {
+            return None;
+        }
+        let delay = match res.headers.get::<RetryAfter>() {
+            Some(&Delay(delay)) => delay,
+            Some(&DateTime(tm)) => {
+                let delay = tm.to_timespec() - now_utc().to_timespec();
+                if delay < Duration::zero() { Duration::zero() } else { delay }
+            },
+            None => return None
+        };
+        if delay > self.max_retry_after {
+            debug!("Retry-After of {} is too long to wait", delay);
+            return None;
+        }
+        Some(delay)
+    }
+}
+
+/// Whether `err` is a failure of the connection, rather than of the
+/// request, so that sending the request again may succeed.
+///
+/// This includes an `EndOfFile` before the response, which is how a pooled
+/// connection that the server has closed usually fails, and a body that
+/// was cut short.
+pub fn is_transient(err: &HttpError) -> bool {
+    match *err {
+        HttpIoError(ref e) => match e.kind {
+            ConnectionRefused | ConnectionReset | ConnectionAborted |
+                BrokenPipe | EndOfFile => true,
+            _ => is_incomplete(e)
+        },
+        _ => false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{IoError, EndOfFile, InvalidInput, OtherIoError};
+    use std::time::Duration;
+
+    use method::Method::{Get, Put, Post};
+    use HttpError::{HttpIoError, HttpUriError};
+    use super::{RetryPolicy, is_transient};
+
+    fn io_error(kind: ::std::io::IoErrorKind) -> ::HttpError {
+        HttpIoError(IoError { kind: kind, desc: "test", detail: None })
+    }
+
+    #[test]
+    fn test_backoff() {
+        let mut policy = RetryPolicy::new();
+        policy.set_backoff(Duration::milliseconds(100), Duration::seconds(1));
+        for _ in range(0u, 20) {
+            let first = policy.backoff(1);
+            assert!(first >= Duration::milliseconds(50) && first <= Duration::milliseconds(100));
+            let third = policy.backoff(3);
+            assert!(third >= Duration::milliseconds(200) && third <= Duration::milliseconds(400));
+            let capped = policy.backoff(40);
+            assert!(capped >= Duration::milliseconds(500) && capped <= Duration::seconds(1));
+        }
+    }
+
+    #[test]
+    fn test_retry_error() {
+        let policy = RetryPolicy::new();
+        let eof = io_error(EndOfFile);
+        assert!(policy.retry_error(&Get, &eof, 1).is_some());
+        assert!(policy.retry_error(&Put, &eof, 2).is_some());
+        assert!(policy.retry_error(&Get, &eof, 3).is_none());
+        assert!(policy.retry_error(&Post, &eof, 1).is_none());
+        assert!(RetryPolicy::never().retry_error(&Get, &eof, 1).is_none());
+    }
+
+    #[test]
+    fn test_is_transient() {
+        assert!(is_transient(&io_error(EndOfFile)));
+        assert!(!is_transient(&io_error(InvalidInput)));
+        let incomplete = IoError { kind: OtherIoError, desc: "incomplete message", detail: None };
+        assert!(is_transient(&HttpIoError(incomplete)));
+        assert!(!is_transient(&HttpUriError));
+    }
+}
@@ -0,0 +1,378 @@
+//! Request Signing
+//!
+//! Some services authenticate each request with a signature of its method,
+//! target and headers. A `Signer` computes it once the request is final,
+//! just before its head is written, and adds it to the headers:
+//!
+//! ```no_run
+//! # use hyper::Url;
+//! # use hyper::client::Client;
+//! # use hyper::client::sign::AwsV4;
+//! let mut client = Client::new();
+//! client.set_signer(AwsV4::new("AKIDEXAMPLE", "secret", "us-east-1", "s3"));
+//! let url = Url::parse("https://examplebucket.s3.amazonaws.com/photos/puppy.jpg").unwrap();
+//! let res = client.get(url).send();
+//! ```
+use std::ascii::AsciiExt;
+
+use openssl::crypto::hash::{hash, HashType};
+use openssl::crypto::hmac::HMAC;
+use serialize::hex::ToHex;
+use time::now_utc;
+use url::Url;
+use url::percent_encoding::lossy_utf8_percent_decode;
+
+use form::{mod, uri_encode};
+use header::Headers;
+use header::common::util::one_raw_string;
+use method::Method;
+use HttpError::HttpHeaderError;
+use HttpResult;
+
+use self::Payload::{Bytes, Streamed};
+use self::PayloadSigning::{Signed, Unsigned};
+
+/// The body of a request being signed, as far as it's known when the head
+/// is written.
+#[deriving(Clone, Show)]
+pub enum Payload<'a> {
+    /// The whole body. A request without a body has an empty one.
+    Bytes(&'a [u8]),
+    /// A body that is written after the head, such as one read from a
+    /// `Reader`.
+    Streamed,
+}
+
+/// Signs requests, by adding headers such as `Authorization`.
+pub trait Signer: Send + Sync {
+    /// Sign a `method` request for `url`.
+    ///
+    /// `headers` are the final headers of the request, including `Host`
+    /// and the `Content-Length` or `Transfer-Encoding` of its body. An
+    /// error fails the request before anything is written.
+    fn sign(&self, method: &Method, url: &Url, headers: &mut Headers,
+            payload: Payload) -> HttpResult<()>;
+}
+
+impl_for_box!(Signer {
+    fn sign(&self, method: &Method, url: &Url, headers: &mut Headers,
+            payload: Payload) -> HttpResult<()>;
+})
+
+/// Whether an `AwsV4` signature covers the body of a request.
+#[deriving(Clone, PartialEq, Show)]
+pub enum PayloadSigning {
+    /// The SHA-256 of the body is signed.
+    Signed,
+    /// The body isn't signed, and is sent as `UNSIGNED-PAYLOAD`.
+    Unsigned,
+}
+
+/// AWS Signature Version 4, with HMAC-SHA256.
+///
+/// The request is signed with its `X-Amz-Date` header if it has one, or
+/// else the current time, which is then set as its `X-Amz-Date`. Every
+/// header is signed, except those that only concern the next hop, such as
+/// `Connection` and `Proxy-Authorization`. Empty, `.` and `..` segments
+/// are removed from the signed path, except for S3, which signs it as is.
+///
+/// A streamed body can only be signed if the request already has an
+/// `X-Amz-Content-Sha256` header with its hash. Otherwise signing it fails
+/// with `HttpHeaderError`, unless the payload is `Unsigned`.
+#[deriving(Clone)]
+pub struct AwsV4 {
+    access_key: String,
+    secret_key: String,
+    session_token: Option<String>,
+    region: String,
+    service: String,
+    payload: PayloadSigning,
+}
+
+impl AwsV4 {
+    /// A signer with these credentials, for `service` in `region`, such as
+    /// `"s3"` in `"eu-west-1"`. The body is signed.
+    pub fn new(access_key: &str, secret_key: &str, region: &str, service: &str) -> AwsV4 {
+        AwsV4 {
+            access_key: access_key.into_string(),
+            secret_key: secret_key.into_string(),
+            session_token: None,
+            region: region.into_string(),
+            service: service.into_string(),
+            payload: Signed,
+        }
+    }
+
+    /// Set the token of temporary credentials, sent and signed as
+    /// `X-Amz-Security-Token`.
+    pub fn set_session_token(&mut self, token: Option<String>) {
+        self.session_token = token;
+    }
+
+    /// Set whether the body is signed.
+    ///
+    /// An `Unsigned` body doesn't need to be hashed before it is sent, but
+    /// only S3 accepts it.
+    pub fn set_payload_signing(&mut self, payload: PayloadSigning) {
+        self.payload = payload;
+    }
+
+    fn canonical_path(&self, url: &Url) -> String {
+        let path = url.serialize_path().unwrap_or_else(|| "/".into_string());
+        let mut segments = vec![];
+        let mut last = "";
+        for segment in path[].split('/').skip(1) {
+            last = segment;
+            let segment = uri_encode(lossy_utf8_percent_decode(segment.as_bytes())[]);
+            // S3 is the one service that neither normalizes the path nor
+            // encodes it twice
+            if self.service[] == "s3" {
+                segments.push(segment);
+                continue;
+            }
+            match segment[] {
+                "" | "." => (),
+                ".." => { segments.pop(); },
+                _ => segments.push(uri_encode(segment[]))
+            }
+        }
+        let trailing = match last { "" | "." | ".." => true, _ => false };
+        if self.service[] != "s3" && trailing && !segments.is_empty() {
+            segments.push(String::new());
+        }
+        format!("/{}", segments.connect("/"))
+    }
+
+    fn signing_key(&self, date: &str) -> Vec<u8> {
+        let key = format!("AWS4{}", self.secret_key);
+        let key = hmac_sha256(key.as_bytes(), date.as_bytes());
+        let key = hmac_sha256(key[], self.region.as_bytes());
+        let key = hmac_sha256(key[], self.service.as_bytes());
+        hmac_sha256(key[], b"aws4_request")
+    }
+}
+
+impl Signer for AwsV4 {
+    fn sign(&self, method: &Method, url: &Url, headers: &mut Headers,
+            payload: Payload) -> HttpResult<()> {
+        let timestamp = match headers.get_raw("X-Amz-Date").and_then(one_raw_string) {
+            Some(timestamp) => timestamp,
+            None => {
+                let timestamp = now_utc().strftime("%Y%m%dT%H%M%SZ");
+                headers.set_raw("X-Amz-Date", vec![timestamp.clone().into_bytes()]);
+                timestamp
+            }
+        };
+        if timestamp.len() < 8 {
+            return Err(HttpHeaderError);
+        }
+        if let Some(ref token) = self.session_token {
+            headers.set_raw("X-Amz-Security-Token", vec![token.clone().into_bytes()]);
+        }
+
+        let given = headers.get_raw("X-Amz-Content-Sha256").and_then(one_raw_string);
+        let payload_hash = match (given, self.payload, payload) {
+            (Some(given), _, _) => given,
+            (None, Unsigned, _) => "UNSIGNED-PAYLOAD".into_string(),
+            (None, Signed, Bytes(body)) => hash(HashType::SHA256, body).to_hex(),
+            (None, Signed, Streamed) => {
+                debug!("can't sign a streamed body without its X-Amz-Content-Sha256");
+                return Err(HttpHeaderError);
+            }
+        };
+        if self.payload == Unsigned || self.service[] == "s3" {
+            headers.set_raw("X-Amz-Content-Sha256", vec![payload_hash.clone().into_bytes()]);
+        }
+
+        let mut names: Vec<String> = headers.iter()
+            .map(|header| header.name().to_ascii_lower())
+            .filter(|name| !UNSIGNED_HEADERS.contains(&name[]))
+            .collect();
+        names.sort();
+        let mut canonical_headers = String::new();
+        for name in names.iter() {
+            // each value is trimmed, with its runs of spaces collapsed
+            let values: Vec<String> = headers.get_raw(name[]).unwrap().iter().map(|value| {
+                let value = String::from_utf8_lossy(value[]);
+                value.words().collect::<Vec<&str>>().connect(" ")
+            }).collect();
+            canonical_headers.push_str(format!("{}:{}\n", name, values.connect(","))[]);
+        }
+        let signed_headers = names.connect(";");
+
+        let mut query: Vec<(String, String)> = form::raw_query_pairs(url).into_iter()
+            .map(|(name, value)| (uri_encode(name[]), uri_encode(value[])))
+            .collect();
+        query.sort();
+        let query: Vec<String> = query.into_iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+
+        let canonical_request = format!("{}\n{}\n{}\n{}\n{}\n{}", method, self.canonical_path(url),
+                                        query.connect("&"), canonical_headers, signed_headers,
+                                        payload_hash);
+        debug!("canonical request [\n{}]", canonical_request);
+
+        let date = timestamp[..8];
+        let scope = format!("{}/{}/{}/aws4_request", date, self.region, self.service);
+        let string_to_sign = format!("AWS4-HMAC-SHA256\n{}\n{}\n{}", timestamp, scope,
+                                     hash(HashType::SHA256, canonical_request.as_bytes()).to_hex());
+        let signature = hmac_sha256(self.signing_key(date)[], string_to_sign.as_bytes()).to_hex();
+
+        let authorization = format!("AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, \
+                                     Signature={}",
+                                    self.access_key, scope, signed_headers, signature);
+        headers.set_raw("Authorization", vec![authorization.into_bytes()]);
+        Ok(())
+    }
+}
+
+// Headers that a proxy may change or remove, and the signature itself.
+static UNSIGNED_HEADERS: &'static [&'static str] = &[
+    "authorization", "connection", "keep-alive", "proxy-authorization", "te", "trailer",
+    "transfer-encoding", "upgrade",
+];
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut hmac = HMAC::new(HashType::SHA256, key);
+    hmac.update(data);
+    hmac.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use url::Url;
+
+    use header::Headers;
+    use method::Method::{mod, Get, Post};
+    use HttpError::HttpHeaderError;
+    use super::{AwsV4, Signer};
+    use super::Payload::{Bytes, Streamed};
+    use super::PayloadSigning::Unsigned;
+
+    // The credentials and date of the AWS Signature Version 4 test suite.
+    fn signer(service: &str) -> AwsV4 {
+        AwsV4::new("AKIDEXAMPLE", "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY", "us-east-1", service)
+    }
+
+    fn headers() -> Headers {
+        let mut headers = Headers::new();
+        headers.set_raw("Host", vec![b"example.amazonaws.com".to_vec()]);
+        headers.set_raw("X-Amz-Date", vec![b"20150830T123600Z".to_vec()]);
+        headers
+    }
+
+    fn authorization(signer: &AwsV4, method: Method, target: &str, mut headers: Headers,
+                     body: &[u8]) -> String {
+        let url = Url::parse(format!("https://example.amazonaws.com{}", target)[]).unwrap();
+        signer.sign(&method, &url, &mut headers, Bytes(body)).unwrap();
+        String::from_utf8(headers.get_raw("Authorization").unwrap()[0].clone()).unwrap()
+    }
+
+    #[test]
+    fn test_get_vanilla() {
+        let auth = authorization(&signer("service"), Get, "/", headers(), b"");
+        assert_eq!(auth[], "AWS4-HMAC-SHA256 \
+                    Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+                    SignedHeaders=host;x-amz-date, \
+                    Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31");
+    }
+
+    #[test]
+    fn test_get_slashes() {
+        let auth = authorization(&signer("service"), Get, "//example//", headers(), b"");
+        assert!(auth[].ends_with(
+            "Signature=9a624bd73a37c9a373b5312afbebe7a714a789de108f0bdfe846570885f57e84"));
+    }
+
+    #[test]
+    fn test_get_relative() {
+        let auth = authorization(&signer("service"), Get, "/example/..", headers(), b"");
+        assert!(auth[].ends_with(
+            "Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"));
+    }
+
+    // The test suite encodes the path only once, so its get-space and
+    // get-utf8 signatures don't apply to the services that encode it twice.
+    #[test]
+    fn test_canonical_path() {
+        let path = |service: &str, target: &str| {
+            let url = Url::parse(format!("https://example.amazonaws.com{}", target)[]).unwrap();
+            signer(service).canonical_path(&url)
+        };
+        assert_eq!(path("service", "/example space/")[], "/example%2520space/");
+        assert_eq!(path("service", "/\u1234")[], "/%25E1%2588%25B4");
+        assert_eq!(path("service", "//example//")[], "/example/");
+        assert_eq!(path("service", "/example/..")[], "/");
+        assert_eq!(path("service", "/example1/example2/../..")[], "/");
+        assert_eq!(path("service", "/./")[], "/");
+        assert_eq!(path("s3", "/example space/")[], "/example%20space/");
+        assert_eq!(path("s3", "//example//")[], "//example//");
+    }
+
+    #[test]
+    fn test_get_s3_space() {
+        let auth = authorization(&signer("s3"), Get, "/example space/puppy.jpg", headers(), b"");
+        assert!(auth[].contains("SignedHeaders=host;x-amz-content-sha256;x-amz-date, "));
+        assert!(auth[].ends_with(
+            "Signature=eb70a9e793584c5efdce00b3eb81c9d688b1842f570c6c1d515f6ad8e950090a"));
+    }
+
+    #[test]
+    fn test_get_vanilla_query_order_key_case() {
+        let auth = authorization(&signer("service"), Get, "/?Param2=value2&Param1=value1",
+                                 headers(), b"");
+        assert!(auth[].ends_with(
+            "Signature=b97d918cfa904a5beff61c982a1b6f458b799221646efd99d3219ec94cdf2500"));
+    }
+
+    // Not in the test suite, but computed the same way: a literal `+` is
+    // signed as `%2B`, and only `%20` as a space.
+    #[test]
+    fn test_get_vanilla_query_plus() {
+        let auth = authorization(&signer("service"), Get, "/?Param1=a+b&Param2=c%20d",
+                                 headers(), b"");
+        assert!(auth[].ends_with(
+            "Signature=1c55f19ac0d51ae388317ed6b93afdf5a41dced6790a0ad53425870269dcfd90"));
+    }
+
+    #[test]
+    fn test_post_x_www_form_urlencoded() {
+        let mut headers = headers();
+        headers.set_raw("Content-Type", vec![b"application/x-www-form-urlencoded".to_vec()]);
+        let auth = authorization(&signer("service"), Post, "/", headers, b"Param1=value1");
+        assert!(auth[].contains("SignedHeaders=content-type;host;x-amz-date, "));
+        assert!(auth[].ends_with(
+            "Signature=ff11897932ad3f4e8b18135d722051e5ac45fc38421b1da7b9d196a0fe09473a"));
+    }
+
+    #[test]
+    fn test_post_sts_token() {
+        let mut signer = signer("service");
+        signer.set_session_token(Some("AQoDYXdzEPT//////////wEXAMPLEtc764bNrC9SAPBSM22wDOk4x4HIZ8j4\
+                                       FZTwdQWLWsKWHGBuFqwAeMicRXmxfpSPfIeoIYRqTflfKD8YUuwthAx7mSEI\
+                                       /qkPpKPi/kMcGdQrmGdeehM4IC1NtBmUpp2wUE8phUZampKsburEDy0KPkyQ\
+                                       DYwT7WZ0wq5VSXDvp75YU9HFvlRd8Tx6q6fE8YQcHNVXAkiY9q6d+xo0rKwT\
+                                       38xVqr7ZD0u0iPPkUL64lIZbqBAz+scqKmlzm8FDrypNC9Yjc8fPOLn9FX9K\
+                                       SYvKTr4rvx3iSIlTJabIQwj2ICCR/oLxBA==".into_string()));
+        let auth = authorization(&signer, Post, "/", headers(), b"");
+        assert!(auth[].ends_with(
+            "Signature=85d96828115b5dc0cfc3bd16ad9e210dd772bbebba041836c64533a82be05ead"));
+    }
+
+    #[test]
+    fn test_unsigned_payload() {
+        let url = Url::parse("https://example.amazonaws.com/").unwrap();
+        let mut signer = signer("s3");
+        let mut headers = headers();
+        assert_eq!(signer.sign(&Post, &url, &mut headers, Streamed), Err(HttpHeaderError));
+
+        signer.set_payload_signing(Unsigned);
+        signer.sign(&Post, &url, &mut headers, Streamed).unwrap();
+        assert_eq!(headers.get_raw("X-Amz-Content-Sha256").unwrap(),
+                   [b"UNSIGNED-PAYLOAD".to_vec()][]);
+        let auth = headers.get_raw("Authorization").unwrap()[0].clone();
+        assert!(String::from_utf8(auth).unwrap()[]
+                .contains("SignedHeaders=host;x-amz-content-sha256;x-amz-date, "));
+    }
+}
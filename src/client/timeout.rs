@@ -0,0 +1,100 @@
+//! Client Timeouts
+//!
+//! A request can be limited both by how long a single operation on its
+//! connection may block, and by a deadline for the whole request.
+use std::io::{IoError, IoResult, TimedOut};
+use std::time::Duration;
+
+use time::precise_time_ns;
+
+/// The timeouts of a client request.
+///
+/// `connect`, `read` and `write` limit how long a single operation may
+/// block, while `total` limits the whole request: connecting, sending,
+/// following redirects, and reading the response body.
+///
+/// When a timeout expires, the request fails with an `HttpTimeoutError`, or
+/// a read of the response body fails with a `TimedOut` IoError.
+#[deriving(Clone, Default, PartialEq, Show)]
+pub struct Timeouts {
+    /// How long to wait for a connection, including the TLS handshake.
+    pub connect: Option<Duration>,
+    /// How long a single read may wait for data.
+    pub read: Option<Duration>,
+    /// How long a single write may block.
+    pub write: Option<Duration>,
+    /// How long the whole request may take.
+    pub total: Option<Duration>,
+}
+
+/// A point in time a request has to be done by.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Deadline(u64);
+
+impl Deadline {
+    /// The deadline `timeout` from now.
+    pub fn after(timeout: Duration) -> Deadline {
+        Deadline(precise_time_ns() + timeout.num_nanoseconds().unwrap_or(0) as u64)
+    }
+
+    /// The time left until the deadline.
+    ///
+    /// Fails with a `TimedOut` error once the deadline has passed.
+    pub fn remaining(&self) -> IoResult<Duration> {
+        let now = precise_time_ns();
+        if now >= self.0 {
+            debug!("deadline exceeded");
+            Err(IoError {
+                kind: TimedOut,
+                desc: "Request deadline exceeded",
+                detail: None
+            })
+        } else {
+            Ok(Duration::nanoseconds((self.0 - now) as i64))
+        }
+    }
+}
+
+/// The timeout for an operation limited by both `timeout` and `deadline`.
+pub fn effective(timeout: Option<Duration>, deadline: Option<&Deadline>) -> IoResult<Option<Duration>> {
+    match deadline {
+        Some(deadline) => {
+            let remaining = try!(deadline.remaining());
+            Ok(Some(match timeout {
+                Some(timeout) if timeout < remaining => timeout,
+                _ => remaining
+            }))
+        },
+        None => Ok(timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::TimedOut;
+    use std::time::Duration;
+
+    use super::{Deadline, effective};
+
+    #[test]
+    fn test_effective_without_deadline() {
+        assert_eq!(effective(None, None), Ok(None));
+        assert_eq!(effective(Some(Duration::seconds(5)), None), Ok(Some(Duration::seconds(5))));
+    }
+
+    #[test]
+    fn test_effective_with_deadline() {
+        let deadline = Deadline::after(Duration::seconds(60));
+        assert_eq!(effective(Some(Duration::seconds(5)), Some(&deadline)),
+                   Ok(Some(Duration::seconds(5))));
+        let remaining = effective(None, Some(&deadline)).unwrap().unwrap();
+        assert!(remaining > Duration::seconds(50) && remaining <= Duration::seconds(60));
+    }
+
+    #[test]
+    fn test_deadline_passed() {
+        let deadline = Deadline::after(Duration::zero());
+        assert_eq!(effective(Some(Duration::seconds(5)), Some(&deadline)).unwrap_err().kind,
+                   TimedOut);
+    }
+}
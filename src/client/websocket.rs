@@ -0,0 +1,547 @@
+//! Client WebSockets
+//!
+//! A WebSocket starts as an HTTP request, which the server answers with
+//! `101 Switching Protocols`. From then on, the connection carries
+//! messages in both directions, as described in RFC 6455:
+//!
+//! ```no_run
+//! # use hyper::Url;
+//! # use hyper::client::Client;
+//! # use hyper::client::websocket::Message;
+//! let client = Client::new();
+//! let url = Url::parse("ws://example.domain/chat").unwrap();
+//! let mut socket = client.websocket(url, &["chat"]).unwrap();
+//! socket.send_text("Hello").unwrap();
+//! match socket.recv().unwrap() {
+//!     Message::Text(text) => println!("{}", text),
+//!     _ => ()
+//! }
+//! socket.close(1000, "").unwrap();
+//! ```
+//!
+//! Pings from the server are answered as they are received, and
+//! fragmented messages are put back together before being returned.
+use std::ascii::AsciiExt;
+use std::cmp::min;
+use std::io::{BufferedReader, IoError, IoResult, InvalidInput, EndOfFile, NotConnected,
+              standard_error};
+use std::rand::{task_rng, Rng};
+use std::str::from_utf8;
+
+use openssl::crypto::hash::{hash, HashType};
+use serialize::base64::{ToBase64, Standard, Config};
+
+use client::Request;
+use header::common::{Connection, Upgrade};
+use header::common::connection::ConnectionHeader;
+use header::common::upgrade::Protocol;
+use header::common::util::one_raw_string;
+use net::{Fresh, NetworkStream};
+use status::StatusCode;
+use HttpError::{HttpHeaderError, HttpStatusError};
+use HttpResult;
+
+use self::Message::{Text, Binary, Pong, Close};
+
+static GUID: &'static str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+const CONTINUATION: u8 = 0x0;
+const TEXT: u8 = 0x1;
+const BINARY: u8 = 0x2;
+const CLOSE: u8 = 0x8;
+const PING: u8 = 0x9;
+const PONG: u8 = 0xA;
+
+/// A message received on a WebSocket.
+#[deriving(Clone, PartialEq, Show)]
+pub enum Message {
+    /// A text message.
+    Text(String),
+    /// A binary message.
+    Binary(Vec<u8>),
+    /// An answer to a ping, with the data of the ping.
+    Pong(Vec<u8>),
+    /// The server closed the WebSocket, with a status code and a reason,
+    /// if it gave them.
+    ///
+    /// The close has already been answered, and nothing more can be
+    /// received.
+    Close(Option<u16>, String),
+}
+
+/// A WebSocket, opened with a handshake.
+pub struct WebSocket {
+    stream: BufferedReader<Box<NetworkStream + Send>>,
+    protocol: Option<String>,
+    // the opcode and data of a fragmented message, until its last frame
+    fragments: Option<(u8, Vec<u8>)>,
+    max_frame_size: Option<uint>,
+    max_message_size: uint,
+    close_sent: bool,
+    close_received: bool,
+}
+
+impl WebSocket {
+    /// Open a WebSocket with a GET request, asking for one of `protocols`,
+    /// if there are any.
+    ///
+    /// Fails with `HttpStatusError` if the server answers with a status
+    /// other than `101 Switching Protocols`, and with `HttpHeaderError` if
+    /// its answer isn't a valid WebSocket handshake.
+    pub fn handshake(req: Request<Fresh>, protocols: &[&str]) -> HttpResult<WebSocket> {
+        let key = task_rng().gen_iter::<u8>().take(16).collect::<Vec<u8>>().to_base64(Config {
+            char_set: Standard,
+            pad: true,
+            line_length: None
+        });
+        WebSocket::handshake_with_key(req, protocols, key)
+    }
+
+    fn handshake_with_key(mut req: Request<Fresh>, protocols: &[&str],
+                          key: String) -> HttpResult<WebSocket> {
+        // the response has no body to decode
+        req.set_decompress(false);
+        {
+            let headers = req.headers_mut();
+            headers.set(Upgrade(vec![Protocol::WebSocket]));
+            headers.set(Connection(vec![ConnectionHeader("Upgrade".into_string())]));
+            headers.set_raw("Sec-WebSocket-Key", vec![key.clone().into_bytes()]);
+            headers.set_raw("Sec-WebSocket-Version", vec![b"13".to_vec()]);
+            if !protocols.is_empty() {
+                let protocols = protocols.connect(", ");
+                headers.set_raw("Sec-WebSocket-Protocol", vec![protocols.into_bytes()]);
+            }
+        }
+        let res = try!(try!(req.start()).send());
+
+        if res.status != StatusCode::SwitchingProtocols {
+            debug!("websocket handshake answered with {}", res.status);
+            return Err(HttpStatusError);
+        }
+        let upgraded = res.headers.get::<Upgrade>()
+            .map_or(false, |upgrade| upgrade.0.contains(&Protocol::WebSocket));
+        let connection = res.headers.get::<Connection>().map_or(false, |connection| {
+            connection.0.iter().any(|option| {
+                option.to_string().to_ascii_lower()[] == "upgrade"
+            })
+        });
+        if !upgraded || !connection {
+            debug!("websocket handshake without Upgrade: websocket");
+            return Err(HttpHeaderError);
+        }
+        let accept = res.headers.get_raw("Sec-WebSocket-Accept").and_then(one_raw_string);
+        if accept != Some(accept_key(key[])) {
+            debug!("websocket handshake with the wrong Sec-WebSocket-Accept");
+            return Err(HttpHeaderError);
+        }
+        let protocol = res.headers.get_raw("Sec-WebSocket-Protocol").and_then(one_raw_string);
+        if let Some(ref protocol) = protocol {
+            if !protocols.contains(&protocol[]) {
+                debug!("websocket handshake chose protocol {}, which wasn't offered", protocol);
+                return Err(HttpHeaderError);
+            }
+        }
+        if res.headers.get_raw("Sec-WebSocket-Extensions").is_some() {
+            debug!("websocket handshake with extensions, which weren't offered");
+            return Err(HttpHeaderError);
+        }
+
+        Ok(WebSocket::new(try!(res.into_buffered()), protocol))
+    }
+
+    fn new(stream: BufferedReader<Box<NetworkStream + Send>>,
+           protocol: Option<String>) -> WebSocket {
+        WebSocket {
+            stream: stream,
+            protocol: protocol,
+            fragments: None,
+            max_frame_size: None,
+            max_message_size: 16 * 1024 * 1024,
+            close_sent: false,
+            close_received: false,
+        }
+    }
+
+    /// The protocol the server chose, among those asked for.
+    #[inline]
+    pub fn protocol(&self) -> Option<&str> { self.protocol.as_ref().map(|p| p[]) }
+
+    /// Set the largest frame to send. By default, every message is sent in
+    /// a single frame.
+    ///
+    /// Longer messages are sent in fragments, so that a large message can
+    /// be interleaved with pings.
+    pub fn set_max_frame_size(&mut self, size: Option<uint>) {
+        self.max_frame_size = size;
+    }
+
+    /// Set the largest message to receive. Defaults to 16 MiB.
+    ///
+    /// A longer message closes the WebSocket with status code 1009, and
+    /// fails with an IoError.
+    pub fn set_max_message_size(&mut self, size: uint) {
+        self.max_message_size = size;
+    }
+
+    /// Send a text message.
+    pub fn send_text(&mut self, text: &str) -> IoResult<()> {
+        self.send_message(TEXT, text.as_bytes())
+    }
+
+    /// Send a binary message.
+    pub fn send_binary(&mut self, data: &[u8]) -> IoResult<()> {
+        self.send_message(BINARY, data)
+    }
+
+    /// Send a ping, which the server answers with a `Pong` of the same
+    /// data, of at most 125 bytes.
+    pub fn ping(&mut self, data: &[u8]) -> IoResult<()> {
+        if data.len() > 125 {
+            return Err(protocol_error("a ping has at most 125 bytes"));
+        }
+        try!(self.check_open());
+        self.write_frame(true, PING, data)
+    }
+
+    /// Close the WebSocket with a status code, such as 1000 for a normal
+    /// closure, and a reason, cut to its first 123 bytes.
+    ///
+    /// Codes that are reserved, such as 1005 and 1006, fail with
+    /// `InvalidInput`. Messages still received before the server's own
+    /// close are dropped.
+    pub fn close(&mut self, code: u16, reason: &str) -> IoResult<()> {
+        match code {
+            1000...1003 | 1007...1014 | 3000...4999 => (),
+            _ => return Err(protocol_error("a reserved close code"))
+        }
+        try!(self.check_open());
+        try!(self.send_close(code, reason));
+        while !self.close_received {
+            try!(self.recv());
+        }
+        Ok(())
+    }
+
+    /// Receive the next message.
+    ///
+    /// Once the WebSocket is closed, this fails with `EndOfFile`. A frame
+    /// that breaks the protocol closes the WebSocket, and fails with an
+    /// IoError.
+    pub fn recv(&mut self) -> IoResult<Message> {
+        if self.close_received {
+            return Err(standard_error(EndOfFile));
+        }
+        loop {
+            let (fin, opcode, payload) = try!(self.read_frame());
+            match opcode {
+                PING => {
+                    if !self.close_sent {
+                        try!(self.write_frame(true, PONG, payload[]));
+                    }
+                    continue;
+                },
+                PONG => return Ok(Pong(payload)),
+                CLOSE => return self.closed_by_peer(payload),
+                _ => ()
+            }
+
+            let (opcode, data) = match (opcode, self.fragments.take()) {
+                (CONTINUATION, Some((opcode, mut data))) => {
+                    data.push_all(payload[]);
+                    (opcode, data)
+                },
+                (TEXT, None) | (BINARY, None) => (opcode, payload),
+                (CONTINUATION, None) => return Err(self.fail(1002, "unexpected continuation")),
+                (TEXT, Some(_)) | (BINARY, Some(_)) => {
+                    return Err(self.fail(1002, "a message within a fragmented message"));
+                },
+                _ => return Err(self.fail(1002, "unknown opcode"))
+            };
+            if data.len() > self.max_message_size {
+                return Err(self.fail(1009, "message too big"));
+            }
+            if !fin {
+                self.fragments = Some((opcode, data));
+                continue;
+            }
+
+            if opcode == BINARY {
+                return Ok(Binary(data));
+            }
+            return match String::from_utf8(data) {
+                Ok(text) => Ok(Text(text)),
+                Err(_) => Err(self.fail(1007, "text message that isn't UTF-8"))
+            };
+        }
+    }
+
+    /// Consumes the WebSocket to return the NetworkStream underneath.
+    pub fn into_inner(self) -> Box<NetworkStream + Send> {
+        self.stream.into_inner()
+    }
+
+    fn check_open(&self) -> IoResult<()> {
+        if self.close_sent {
+            Err(standard_error(NotConnected))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn send_message(&mut self, opcode: u8, data: &[u8]) -> IoResult<()> {
+        try!(self.check_open());
+        let size = match self.max_frame_size {
+            Some(size) if size > 0 => size,
+            _ => data.len()
+        };
+        let mut sent = 0;
+        let mut opcode = opcode;
+        loop {
+            let end = min(sent + size, data.len());
+            try!(self.write_frame(end == data.len(), opcode, data[sent..end]));
+            if end == data.len() {
+                return Ok(());
+            }
+            sent = end;
+            opcode = CONTINUATION;
+        }
+    }
+
+    fn send_close(&mut self, code: u16, reason: &str) -> IoResult<()> {
+        // the reason stays UTF-8, in a control frame of 125 bytes at most
+        let mut end = min(reason.len(), 123);
+        while !reason.is_char_boundary(end) {
+            end -= 1;
+        }
+        let mut payload = vec![(code >> 8) as u8, code as u8];
+        payload.push_all(reason[..end].as_bytes());
+        self.close_sent = true;
+        self.write_frame(true, CLOSE, payload[])
+    }
+
+    // Answer a close frame with one of our own, unless we sent one first.
+    fn closed_by_peer(&mut self, payload: Vec<u8>) -> IoResult<Message> {
+        self.close_received = true;
+        let (code, reason) = match payload.len() {
+            0 => (None, String::new()),
+            1 => return Err(self.fail(1002, "close frame with a partial status code")),
+            _ => {
+                let code = (payload[0] as u16) << 8 | payload[1] as u16;
+                match from_utf8(payload[2..]) {
+                    Some(reason) => (Some(code), reason.into_string()),
+                    None => return Err(self.fail(1007, "close reason that isn't UTF-8"))
+                }
+            }
+        };
+        if !self.close_sent {
+            try!(self.send_close(code.unwrap_or(1000), ""));
+        }
+        Ok(Close(code, reason))
+    }
+
+    // Close the WebSocket after a protocol error, and describe the error.
+    fn fail(&mut self, code: u16, detail: &'static str) -> IoError {
+        debug!("websocket protocol error: {}", detail);
+        if !self.close_sent {
+            let _ = self.send_close(code, "");
+        }
+        self.close_received = true;
+        protocol_error(detail)
+    }
+
+    fn read_frame(&mut self) -> IoResult<(bool, u8, Vec<u8>)> {
+        let first = try!(self.stream.read_byte());
+        let second = try!(self.stream.read_byte());
+        let fin = first & 0x80 != 0;
+        let opcode = first & 0x0F;
+        if first & 0x70 != 0 {
+            return Err(self.fail(1002, "reserved bits set, without an extension"));
+        }
+        if second & 0x80 != 0 {
+            return Err(self.fail(1002, "masked frame from the server"));
+        }
+        let len = match second & 0x7F {
+            126 => try!(self.stream.read_be_u16()) as u64,
+            127 => try!(self.stream.read_be_u64()),
+            len => len as u64
+        };
+        if opcode >= CLOSE && (!fin || len > 125) {
+            return Err(self.fail(1002, "fragmented or long control frame"));
+        }
+        if len > self.max_message_size as u64 {
+            return Err(self.fail(1009, "message too big"));
+        }
+        let payload = try!(self.stream.read_exact(len as uint));
+        Ok((fin, opcode, payload))
+    }
+
+    // Frames sent by a client are masked with a random key.
+    fn write_frame(&mut self, fin: bool, opcode: u8, payload: &[u8]) -> IoResult<()> {
+        let mut frame = Vec::with_capacity(payload.len() + 14);
+        frame.push(if fin { 0x80 | opcode } else { opcode });
+        let len = payload.len();
+        if len < 126 {
+            frame.push(0x80 | len as u8);
+        } else if len <= 0xFFFF {
+            frame.push(0x80 | 126);
+            frame.push((len >> 8) as u8);
+            frame.push(len as u8);
+        } else {
+            frame.push(0x80 | 127);
+            for i in range(0u, 8).rev() {
+                frame.push((len as u64 >> (i * 8)) as u8);
+            }
+        }
+        let key = task_rng().gen::<u32>();
+        let mask = [(key >> 24) as u8, (key >> 16) as u8, (key >> 8) as u8, key as u8];
+        frame.push_all(mask[]);
+        frame.extend(payload.iter().enumerate().map(|(i, &b)| b ^ mask[i % 4]));
+
+        let stream = self.stream.get_mut();
+        try!(stream.write(frame[]));
+        stream.flush()
+    }
+}
+
+fn protocol_error(detail: &'static str) -> IoError {
+    IoError {
+        kind: InvalidInput,
+        desc: "websocket protocol error",
+        detail: Some(detail.into_string()),
+    }
+}
+
+/// The `Sec-WebSocket-Accept` of a handshake with this `Sec-WebSocket-Key`.
+pub fn accept_key(key: &str) -> String {
+    let mut input = key.into_string();
+    input.push_str(GUID);
+    hash(HashType::SHA1, input.as_bytes()).to_base64(Config {
+        char_set: Standard,
+        pad: true,
+        line_length: None
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::boxed::BoxAny;
+    use std::io::{BufferedReader, InvalidInput};
+    use std::str::from_utf8;
+    use url::Url;
+
+    use client::Client;
+    use mock::{MockStream, ScriptedConnector};
+    use net::NetworkStream;
+    use super::{WebSocket, accept_key};
+    use super::Message::{Text, Binary, Close};
+
+    fn open(input: &[u8]) -> WebSocket {
+        let stream = box MockStream::with_input(input) as Box<NetworkStream + Send>;
+        WebSocket::new(BufferedReader::new(stream), None)
+    }
+
+    // The frames written to the socket, unmasked.
+    fn written(socket: WebSocket) -> Vec<(u8, Vec<u8>)> {
+        let stream = *socket.into_inner().downcast::<MockStream>().unwrap();
+        let bytes = stream.write.into_inner();
+        let mut frames = vec![];
+        let mut i = 0;
+        while i < bytes.len() {
+            assert!(bytes[i + 1] & 0x80 != 0, "client frames are masked");
+            let len = (bytes[i + 1] & 0x7F) as uint;
+            assert!(len < 126);
+            let mask = bytes[i + 2..i + 6].to_vec();
+            let payload = bytes[i + 6..i + 6 + len].iter().enumerate()
+                .map(|(j, &b)| b ^ mask[j % 4])
+                .collect();
+            frames.push((bytes[i], payload));
+            i += 6 + len;
+        }
+        frames
+    }
+
+    #[test]
+    fn test_accept_key() {
+        assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ==")[], "s3pPLMBiTxaQ9kYGJzzhZRbK+xo=");
+    }
+
+    #[test]
+    fn test_handshake() {
+        let connector = ScriptedConnector::new(&[
+            b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+              Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGJzzhZRbK+xo=\r\n\
+              Sec-WebSocket-Protocol: chat\r\n\r\n\x81\x02hi",
+        ]);
+        let client = Client::with_connector(connector.clone());
+        let req = client.request(::method::Method::Get,
+                                 Url::parse("http://example.domain/chat").unwrap()).unwrap();
+        let mut socket = WebSocket::handshake_with_key(req, &["chat", "superchat"],
+                                                       "dGhlIHNhbXBsZSBub25jZQ==".into_string())
+            .unwrap();
+        assert_eq!(socket.protocol(), Some("chat"));
+        assert_eq!(socket.recv(), Ok(Text("hi".into_string())));
+
+        let written = connector.written();
+        assert!(written[].contains("Upgrade: websocket\r\n"));
+        assert!(written[].contains("Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n"));
+        assert!(written[].contains("Sec-WebSocket-Version: 13\r\n"));
+        assert!(written[].contains("Sec-WebSocket-Protocol: chat, superchat\r\n"));
+    }
+
+    #[test]
+    fn test_handshake_wrong_accept() {
+        let connector = ScriptedConnector::new(&[
+            b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+              Sec-WebSocket-Accept: wrong\r\n\r\n",
+        ]);
+        let client = Client::with_connector(connector);
+        let url = Url::parse("ws://example.domain/").unwrap();
+        assert_eq!(client.websocket(url, &[]).err(), Some(::HttpError::HttpHeaderError));
+    }
+
+    #[test]
+    fn test_fragments_and_ping() {
+        // "Hel", a ping, "lo", then a close with 1000
+        let mut socket = open(b"\x01\x03Hel\x89\x01p\x80\x02lo\x88\x02\x03\xe8");
+        assert_eq!(socket.recv(), Ok(Text("Hello".into_string())));
+        assert_eq!(socket.recv(), Ok(Close(Some(1000), String::new())));
+        assert!(socket.recv().is_err());
+        assert_eq!(written(socket), vec![(0x8A, b"p".to_vec()), (0x88, vec![0x03, 0xe8])]);
+    }
+
+    #[test]
+    fn test_send_fragmented() {
+        let mut socket = open(b"");
+        socket.set_max_frame_size(Some(2));
+        socket.send_binary(b"abcde").unwrap();
+        assert_eq!(written(socket), vec![
+            (0x02, b"ab".to_vec()), (0x00, b"cd".to_vec()), (0x80, b"e".to_vec())
+        ]);
+    }
+
+    #[test]
+    fn test_close() {
+        let mut socket = open(b"\x88\x02\x03\xe8");
+        assert_eq!(socket.close(1005, "").unwrap_err().kind, InvalidInput);
+        assert_eq!(socket.close(999, "").unwrap_err().kind, InvalidInput);
+
+        // 200 bytes of "é", which is cut to 61 of them
+        let reason: String = range(0u, 100).map(|_| 'é').collect();
+        socket.close(1000, reason[]).unwrap();
+        let frames = written(socket);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].1[..2], [0x03, 0xe8][]);
+        assert_eq!(from_utf8(frames[0].1[2..]), Some(reason[..122]));
+    }
+
+    #[test]
+    fn test_protocol_error() {
+        // a masked frame from the server
+        let mut socket = open(b"\x82\x81\x00\x00\x00\x00a");
+        assert!(socket.recv().is_err());
+        assert_eq!(written(socket), vec![(0x88, vec![0x03, 0xea])]);
+
+        let mut socket = open(b"\x82\x01a");
+        assert_eq!(socket.recv(), Ok(Binary(b"a".to_vec())));
+    }
+}
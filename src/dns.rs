@@ -0,0 +1,234 @@
+//! Resolving host names into addresses to connect to.
+//!
+//! The `HttpConnector` asks a `Resolver` for the addresses of a host, and
+//! tries each of them in turn until a connection succeeds. By default the
+//! system's answers are cached for a minute; a `StaticResolver` can pin
+//! hosts to fixed addresses instead, like curl's `--resolve`.
+use std::ascii::AsciiExt;
+use std::collections::HashMap;
+use std::io::{IoResult, IoError, OtherIoError};
+use std::io::net::addrinfo::get_host_addresses;
+use std::io::net::ip::{SocketAddr, IpAddr, Port};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use time::precise_time_ns;
+
+/// Resolves a host into the socket addresses it can be reached at.
+pub trait Resolver: Send + Sync {
+    /// The addresses for `host` and `port`, in the order they should be
+    /// tried.
+    fn resolve(&self, host: &str, port: Port) -> IoResult<Vec<SocketAddr>>;
+}
+
+impl_for_box!(Resolver {
+    fn resolve(&self, host: &str, port: Port) -> IoResult<Vec<SocketAddr>>;
+})
+
+/// Resolves hosts with the system's resolver, on every call.
+#[deriving(Clone)]
+pub struct SystemResolver;
+
+impl Resolver for SystemResolver {
+    fn resolve(&self, host: &str, port: Port) -> IoResult<Vec<SocketAddr>> {
+        // IPv6 literals are serialized in brackets in a Url
+        let host = host.trim_left_chars('[').trim_right_chars(']');
+        let ips = try!(get_host_addresses(host));
+        debug!("resolved {} to {}", host, ips);
+        Ok(to_socket_addrs(ips[], port))
+    }
+}
+
+/// Remembers the answers of another resolver for a while.
+#[deriving(Clone)]
+pub struct CachingResolver<R = SystemResolver> {
+    inner: R,
+    ttl: Duration,
+    cache: Arc<Mutex<HashMap<String, (Vec<IpAddr>, u64)>>>,
+}
+
+impl CachingResolver<SystemResolver> {
+    /// Cache the answers of the system's resolver for a minute.
+    pub fn new() -> CachingResolver<SystemResolver> {
+        CachingResolver::with_resolver(SystemResolver, Duration::minutes(1))
+    }
+}
+
+impl<R: Resolver> CachingResolver<R> {
+    /// Cache the answers of `inner` for `ttl`.
+    ///
+    /// Clones of the CachingResolver share the cache.
+    pub fn with_resolver(inner: R, ttl: Duration) -> CachingResolver<R> {
+        CachingResolver {
+            inner: inner,
+            ttl: ttl,
+            cache: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Forget every cached answer.
+    pub fn clear(&self) {
+        self.cache.lock().clear();
+    }
+}
+
+impl<R: Resolver> Resolver for CachingResolver<R> {
+    fn resolve(&self, host: &str, port: Port) -> IoResult<Vec<SocketAddr>> {
+        let key = host.to_ascii_lower();
+        let now = precise_time_ns();
+        if let Some(&(ref ips, expires)) = self.cache.lock().get(&key) {
+            if now < expires {
+                return Ok(to_socket_addrs(ips[], port));
+            }
+        }
+
+        let addrs = try!(self.inner.resolve(host, port));
+        // an empty answer is likely transient, so ask again next time
+        if addrs.is_empty() {
+            return Ok(addrs);
+        }
+        let ips = addrs.iter().map(|addr| addr.ip).collect();
+        let expires = now + self.ttl.num_nanoseconds().unwrap_or(0) as u64;
+        self.cache.lock().insert(key, (ips, expires));
+        Ok(addrs)
+    }
+}
+
+/// Resolves hosts from a fixed map of addresses, and leaves any other host
+/// to another resolver.
+#[deriving(Clone)]
+pub struct StaticResolver<R = CachingResolver> {
+    hosts: HashMap<(String, Port), Vec<SocketAddr>>,
+    fallback: Option<R>,
+}
+
+impl<R: Resolver> StaticResolver<R> {
+    /// A resolver that knows no hosts at all.
+    pub fn new() -> StaticResolver<R> {
+        StaticResolver {
+            hosts: HashMap::new(),
+            fallback: None,
+        }
+    }
+
+    /// A resolver that asks `fallback` for hosts that aren't in the map.
+    pub fn with_fallback(fallback: R) -> StaticResolver<R> {
+        StaticResolver {
+            hosts: HashMap::new(),
+            fallback: Some(fallback),
+        }
+    }
+
+    /// Resolve `host` and `port` to `addrs`.
+    ///
+    /// Adding addresses for the same host and port again appends them.
+    pub fn add(&mut self, host: &str, port: Port, addrs: &[SocketAddr]) {
+        let key = (host.to_ascii_lower(), port);
+        match self.hosts.get_mut(&key) {
+            Some(existing) => {
+                existing.push_all(addrs);
+                return;
+            },
+            None => ()
+        }
+        self.hosts.insert(key, addrs.to_vec());
+    }
+}
+
+impl<R: Resolver> Resolver for StaticResolver<R> {
+    fn resolve(&self, host: &str, port: Port) -> IoResult<Vec<SocketAddr>> {
+        match self.hosts.get(&(host.to_ascii_lower(), port)) {
+            Some(addrs) => return Ok(addrs.clone()),
+            None => ()
+        }
+        match self.fallback {
+            Some(ref fallback) => fallback.resolve(host, port),
+            None => Err(IoError {
+                kind: OtherIoError,
+                desc: "Host not found in static resolver",
+                detail: Some(format!("{}:{}", host, port))
+            })
+        }
+    }
+}
+
+fn to_socket_addrs(ips: &[IpAddr], port: Port) -> Vec<SocketAddr> {
+    ips.iter().map(|&ip| SocketAddr { ip: ip, port: port }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::IoResult;
+    use std::io::net::ip::{SocketAddr, Ipv4Addr, Port};
+    use std::sync::atomic::{AtomicUint, SeqCst};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use super::{Resolver, CachingResolver, StaticResolver};
+
+    #[deriving(Clone)]
+    struct CountingResolver(Arc<AtomicUint>);
+
+    impl Resolver for CountingResolver {
+        fn resolve(&self, host: &str, port: Port) -> IoResult<Vec<SocketAddr>> {
+            self.0.fetch_add(1, SeqCst);
+            if host == "nowhere.domain" {
+                return Ok(vec![]);
+            }
+            Ok(vec![SocketAddr { ip: Ipv4Addr(10, 0, 0, 1), port: port }])
+        }
+    }
+
+    #[test]
+    fn test_caching_resolver() {
+        let count = Arc::new(AtomicUint::new(0));
+        let resolver = CachingResolver::with_resolver(CountingResolver(count.clone()),
+                                                      Duration::minutes(1));
+        let addrs = resolver.resolve("example.domain", 80).unwrap();
+        assert_eq!(addrs, vec![SocketAddr { ip: Ipv4Addr(10, 0, 0, 1), port: 80 }]);
+        let addrs = resolver.resolve("example.domain", 443).unwrap();
+        assert_eq!(addrs[0].port, 443);
+        resolver.resolve("Example.Domain", 80).unwrap();
+        assert_eq!(count.load(SeqCst), 1);
+
+        resolver.clear();
+        resolver.resolve("example.domain", 80).unwrap();
+        assert_eq!(count.load(SeqCst), 2);
+    }
+
+    #[test]
+    fn test_caching_resolver_expires() {
+        let count = Arc::new(AtomicUint::new(0));
+        let resolver = CachingResolver::with_resolver(CountingResolver(count.clone()),
+                                                      Duration::zero());
+        resolver.resolve("example.domain", 80).unwrap();
+        resolver.resolve("example.domain", 80).unwrap();
+        assert_eq!(count.load(SeqCst), 2);
+    }
+
+    #[test]
+    fn test_caching_resolver_empty() {
+        let count = Arc::new(AtomicUint::new(0));
+        let resolver = CachingResolver::with_resolver(CountingResolver(count.clone()),
+                                                      Duration::minutes(1));
+        assert_eq!(resolver.resolve("nowhere.domain", 80).unwrap(), vec![]);
+        assert_eq!(resolver.resolve("nowhere.domain", 80).unwrap(), vec![]);
+        assert_eq!(count.load(SeqCst), 2);
+    }
+
+    #[test]
+    fn test_static_resolver() {
+        let count = Arc::new(AtomicUint::new(0));
+        let mut resolver = StaticResolver::with_fallback(CountingResolver(count.clone()));
+        let pinned = SocketAddr { ip: Ipv4Addr(127, 0, 0, 1), port: 8080 };
+        resolver.add("Example.Domain", 443, &[pinned]);
+
+        assert_eq!(resolver.resolve("example.domain", 443).unwrap(), vec![pinned]);
+        assert_eq!(count.load(SeqCst), 0);
+        assert_eq!(resolver.resolve("example.domain", 80).unwrap()[0].ip, Ipv4Addr(10, 0, 0, 1));
+        assert_eq!(count.load(SeqCst), 1);
+
+        let resolver: StaticResolver<CountingResolver> = StaticResolver::new();
+        assert!(resolver.resolve("example.domain", 80).is_err());
+    }
+}
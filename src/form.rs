@@ -0,0 +1,194 @@
+//! `application/x-www-form-urlencoded` Forms
+//!
+//! The encoding of HTML form submissions, used both for request bodies and
+//! for query strings. The Client and the Server share these functions, so
+//! that what one of them serializes, the other parses back the same.
+use url::Url;
+
+static HEX: &'static [u8] = b"0123456789ABCDEF";
+
+/// Serialize name/value pairs, such as `name=J%C3%BCrgen+M&lang=de`.
+///
+/// Spaces become `+`, and every byte other than ASCII letters, digits and
+/// `*-._` is percent-encoded.
+pub fn serialize(pairs: &[(&str, &str)]) -> String {
+    let mut out = String::new();
+    for (i, &(name, value)) in pairs.iter().enumerate() {
+        if i > 0 {
+            out.push('&');
+        }
+        encode_into(name, &mut out);
+        out.push('=');
+        encode_into(value, &mut out);
+    }
+    out
+}
+
+/// Parse serialized name/value pairs, in the order they appear.
+///
+/// A pair without a `=` has an empty value, and invalid UTF-8 is replaced.
+pub fn parse(input: &[u8]) -> Vec<(String, String)> {
+    parse_pairs(input, true)
+}
+
+/// Append name/value pairs to the query of `url`, keeping any query it
+/// already has.
+pub fn append_query(url: &mut Url, pairs: &[(&str, &str)]) {
+    if pairs.is_empty() {
+        return;
+    }
+    let serialized = serialize(pairs);
+    url.query = Some(match url.query.take() {
+        Some(mut query) => {
+            if !query.is_empty() {
+                query.push('&');
+            }
+            query.push_str(serialized[]);
+            query
+        },
+        None => serialized
+    });
+}
+
+/// The name/value pairs of the query of `url`.
+pub fn query_pairs(url: &Url) -> Vec<(String, String)> {
+    match url.query {
+        Some(ref query) => parse(query.as_bytes()),
+        None => vec![]
+    }
+}
+
+/// The name/value pairs of the query of `url`, percent-decoded as any part
+/// of a Url is, so that unlike in a form, a `+` stays a `+`.
+pub fn raw_query_pairs(url: &Url) -> Vec<(String, String)> {
+    match url.query {
+        Some(ref query) => parse_pairs(query.as_bytes(), false),
+        None => vec![]
+    }
+}
+
+/// Percent-encode every byte of `s` but the unreserved characters of
+/// RFC 3986, such as `a%2Bb%20c` for `a+b c`.
+pub fn uri_encode(s: &str) -> String {
+    let mut out = String::new();
+    for &b in s.as_bytes().iter() {
+        match b {
+            b'a'...b'z' | b'A'...b'Z' | b'0'...b'9' | b'-' | b'.' | b'_' | b'~' => {
+                out.push(b as char);
+            },
+            _ => push_encoded(b, &mut out)
+        }
+    }
+    out
+}
+
+fn encode_into(s: &str, out: &mut String) {
+    for &b in s.as_bytes().iter() {
+        match b {
+            b'a'...b'z' | b'A'...b'Z' | b'0'...b'9' | b'*' | b'-' | b'.' | b'_' => {
+                out.push(b as char);
+            },
+            b' ' => out.push('+'),
+            _ => push_encoded(b, out)
+        }
+    }
+}
+
+fn push_encoded(b: u8, out: &mut String) {
+    out.push('%');
+    out.push(HEX[(b >> 4) as uint] as char);
+    out.push(HEX[(b & 0xF) as uint] as char);
+}
+
+fn parse_pairs(input: &[u8], plus_is_space: bool) -> Vec<(String, String)> {
+    input.split(|&b| b == b'&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            match pair.iter().position(|&b| b == b'=') {
+                Some(i) => (decode(pair[..i], plus_is_space), decode(pair[i + 1..], plus_is_space)),
+                None => (decode(pair, plus_is_space), String::new())
+            }
+        })
+        .collect()
+}
+
+// Percent-decode `input`, and decode a `+` as a space if `plus_is_space`.
+fn decode(input: &[u8], plus_is_space: bool) -> String {
+    let mut out = Vec::with_capacity(input.len());
+    let mut i = 0;
+    while i < input.len() {
+        match input[i] {
+            b'+' if plus_is_space => out.push(b' '),
+            b'%' if i + 2 < input.len() => {
+                match (from_hex(input[i + 1]), from_hex(input[i + 2])) {
+                    (Some(high), Some(low)) => {
+                        out.push(high << 4 | low);
+                        i += 2;
+                    },
+                    _ => out.push(b'%')
+                }
+            },
+            b => out.push(b)
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(out[]).into_string()
+}
+
+fn from_hex(b: u8) -> Option<u8> {
+    match b {
+        b'0'...b'9' => Some(b - b'0'),
+        b'a'...b'f' => Some(b - b'a' + 10),
+        b'A'...b'F' => Some(b - b'A' + 10),
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use url::Url;
+    use super::{serialize, parse, append_query, query_pairs, raw_query_pairs, uri_encode};
+
+    fn pairs(v: &[(&str, &str)]) -> Vec<(String, String)> {
+        v.iter().map(|&(n, v)| (n.into_string(), v.into_string())).collect()
+    }
+
+    #[test]
+    fn test_serialize() {
+        assert_eq!(serialize(&[("name", "Jürgen M"), ("q", "a&b=c+d/~")])[],
+                   "name=J%C3%BCrgen+M&q=a%26b%3Dc%2Bd%2F%7E");
+        assert_eq!(serialize(&[])[], "");
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse(b"name=J%C3%BCrgen+M&q=a%26b%3Dc%2Bd&&flag&bad=%G1%"),
+                   pairs(&[("name", "Jürgen M"), ("q", "a&b=c+d"), ("flag", ""),
+                           ("bad", "%G1%")]));
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let original = &[("a b", "ä=&+%"), ("", "x")];
+        assert_eq!(parse(serialize(original).as_bytes()), pairs(original));
+    }
+
+    #[test]
+    fn test_append_query() {
+        let mut url = Url::parse("http://example.domain/search?lang=en").unwrap();
+        append_query(&mut url, &[("q", "rust lang")]);
+        assert_eq!(url.query, Some("lang=en&q=rust+lang".into_string()));
+        assert_eq!(query_pairs(&url), pairs(&[("lang", "en"), ("q", "rust lang")]));
+
+        let mut url = Url::parse("http://example.domain/").unwrap();
+        append_query(&mut url, &[("q", "1")]);
+        assert_eq!(url.serialize()[], "http://example.domain/?q=1");
+    }
+
+    #[test]
+    fn test_raw_query_pairs() {
+        let url = Url::parse("http://example.domain/?a=b+c%2B&d%20e").unwrap();
+        assert_eq!(raw_query_pairs(&url), pairs(&[("a", "b+c+"), ("d e", "")]));
+        assert_eq!(uri_encode("b+c d~")[], "b%2Bc%20d~");
+    }
+}
@@ -0,0 +1,37 @@
+use header::{Header, HeaderFormat};
+use std::fmt;
+use super::util::{from_comma_delimited, fmt_comma_delimited};
+use super::transfer_encoding::Encoding;
+
+/// The `Accept-Encoding` header.
+///
+/// The `Accept-Encoding` header can be used by clients to indicate which
+/// content codings they can decode in a response.
+///
+/// ```notrust
+/// Accept-Encoding: gzip, deflate
+/// ```
+///
+/// The implementation uses a vector of `Encoding` values. Quality values
+/// are kept as part of an `EncodingExt`.
+#[deriving(Clone, PartialEq, Show)]
+pub struct AcceptEncoding(pub Vec<Encoding>);
+
+impl Header for AcceptEncoding {
+    fn header_name(_: Option<AcceptEncoding>) -> &'static str {
+        "Accept-Encoding"
+    }
+
+    fn parse_header(raw: &[Vec<u8>]) -> Option<AcceptEncoding> {
+        from_comma_delimited(raw).map(|vec| AcceptEncoding(vec))
+    }
+}
+
+impl HeaderFormat for AcceptEncoding {
+    fn fmt_header(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let AcceptEncoding(ref parts) = *self;
+        fmt_comma_delimited(fmt, parts[])
+    }
+}
+
+bench_header!(bench, AcceptEncoding, { vec![b"gzip, deflate".to_vec()] })
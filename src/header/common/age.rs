@@ -0,0 +1,33 @@
+use std::fmt::{mod, Show};
+use std::time::Duration;
+
+use header::{Header, HeaderFormat};
+use super::util::from_one_raw_str;
+
+/// The `Age` header.
+///
+/// > The "Age" header field conveys the sender's estimate of the amount of
+/// > time since the response was generated or successfully validated at the
+/// > origin server.
+///
+/// It is sent in whole seconds.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Age(pub Duration);
+
+impl Header for Age {
+    fn header_name(_: Option<Age>) -> &'static str {
+        "Age"
+    }
+
+    fn parse_header(raw: &[Vec<u8>]) -> Option<Age> {
+        from_one_raw_str::<u32>(raw).map(|secs| Age(Duration::seconds(secs as i64)))
+    }
+}
+
+impl HeaderFormat for Age {
+    fn fmt_header(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        self.0.num_seconds().fmt(fmt)
+    }
+}
+
+bench_header!(bench, Age, { vec![b"3600".to_vec()] })
@@ -1,7 +1,9 @@
 use std::fmt::{mod, Show};
+use std::num::from_str_radix;
 use std::str::{FromStr, from_utf8};
 use serialize::base64::{ToBase64, FromBase64, Standard, Config};
 use header::{Header, HeaderFormat};
+use super::util::{HeaderParam, parse_auth_params, fmt_quoted};
 
 /// The `Authorization` header field.
 #[deriving(Clone, PartialEq, Show)]
@@ -123,10 +125,109 @@ impl FromStr for Basic {
     }
 }
 
+/// Credentials for Digest Authentication, as described in RFC 7616.
+///
+/// The `response` is computed from a challenge and a password, such as by
+/// `client::auth::digest`.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Digest {
+    /// The name of the user.
+    pub username: String,
+    /// The realm of the challenge.
+    pub realm: String,
+    /// The nonce of the challenge.
+    pub nonce: String,
+    /// The request-target of the request.
+    pub uri: String,
+    /// The hex digest proving the password is known.
+    pub response: String,
+    /// The algorithm the response was computed with, `MD5` if not given.
+    pub algorithm: Option<String>,
+    /// The opaque value of the challenge, sent back unchanged.
+    pub opaque: Option<String>,
+    /// The quality of protection, `auth` or `auth-int`.
+    pub qop: Option<String>,
+    /// How many requests this nonce has been used for, including this one.
+    pub nc: Option<u32>,
+    /// The client's nonce, required with a `qop`.
+    pub cnonce: Option<String>,
+}
+
+impl Scheme for Digest {
+    fn scheme(_: Option<Digest>) -> Option<&'static str> {
+        Some("Digest")
+    }
+
+    fn fmt_scheme(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!("username=".fmt(f));
+        try!(fmt_quoted(f, self.username[]));
+        try!(", realm=".fmt(f));
+        try!(fmt_quoted(f, self.realm[]));
+        try!(", nonce=".fmt(f));
+        try!(fmt_quoted(f, self.nonce[]));
+        try!(", uri=".fmt(f));
+        try!(fmt_quoted(f, self.uri[]));
+        try!(", response=".fmt(f));
+        try!(fmt_quoted(f, self.response[]));
+        if let Some(ref algorithm) = self.algorithm {
+            try!(write!(f, ", algorithm={}", algorithm));
+        }
+        if let Some(ref opaque) = self.opaque {
+            try!(", opaque=".fmt(f));
+            try!(fmt_quoted(f, opaque[]));
+        }
+        if let Some(ref qop) = self.qop {
+            try!(write!(f, ", qop={}", qop));
+        }
+        if let Some(nc) = self.nc {
+            try!(write!(f, ", nc={:08x}", nc));
+        }
+        if let Some(ref cnonce) = self.cnonce {
+            try!(", cnonce=".fmt(f));
+            try!(fmt_quoted(f, cnonce[]));
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Digest {
+    fn from_str(s: &str) -> Option<Digest> {
+        let params = match parse_auth_params(s) {
+            Some(params) => params,
+            None => return None
+        };
+        let param = |name: &str| find_param(params[], name);
+        match (param("username"), param("realm"), param("nonce"), param("uri"),
+               param("response")) {
+            (Some(username), Some(realm), Some(nonce), Some(uri), Some(response)) => {
+                Some(Digest {
+                    username: username,
+                    realm: realm,
+                    nonce: nonce,
+                    uri: uri,
+                    response: response,
+                    algorithm: param("algorithm"),
+                    opaque: param("opaque"),
+                    qop: param("qop"),
+                    nc: param("nc").and_then(|nc| from_str_radix(nc[], 16)),
+                    cnonce: param("cnonce"),
+                })
+            },
+            _ => None
+        }
+    }
+}
+
+fn find_param(params: &[HeaderParam], name: &str) -> Option<String> {
+    params.iter()
+        .find(|&&(ref n, _)| n[] == name)
+        .map(|&(_, ref value)| value.clone())
+}
+
 #[cfg(test)]
 mod tests {
     use std::io::MemReader;
-    use super::{Authorization, Basic};
+    use super::{Authorization, Basic, Digest};
     use super::super::super::{Headers};
 
     fn mem(s: &str) -> MemReader {
@@ -176,6 +277,30 @@ mod tests {
         assert_eq!(auth.0.password, Some("".to_string()));
     }
 
+    #[test]
+    fn test_digest_auth() {
+        let digest = Digest {
+            username: "Mufasa".into_string(),
+            realm: "http-auth@example.org".into_string(),
+            nonce: "7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v".into_string(),
+            uri: "/dir/index.html".into_string(),
+            response: "8ca523f5e9506fed4657c9700eebdbec".into_string(),
+            algorithm: Some("MD5".into_string()),
+            opaque: Some("FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS".into_string()),
+            qop: Some("auth".into_string()),
+            nc: Some(1),
+            cnonce: Some("f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ".into_string()),
+        };
+        let mut headers = Headers::new();
+        headers.set(Authorization(digest.clone()));
+        let s = headers.to_string();
+        assert!(s[].starts_with("Authorization: Digest username=\"Mufasa\", "));
+        assert!(s[].contains(", qop=auth, nc=00000001, "));
+
+        let raw = Headers::from_raw(&mut mem(format!("{}\r\n", s)[])).unwrap();
+        assert_eq!(raw.get::<Authorization<Digest>>(), Some(&Authorization(digest)));
+    }
+
 }
 
 bench_header!(raw, Authorization<String>, { vec![b"foo bar baz".to_vec()] })
@@ -0,0 +1,158 @@
+use header::{Header, HeaderFormat};
+use std::ascii::AsciiExt;
+use std::fmt::{mod, Show};
+use std::str::{FromStr, from_utf8};
+use super::util::fmt_comma_delimited;
+
+use self::CacheDirective::{NoCache, NoStore, NoTransform, OnlyIfCached, MaxAge, MaxStale,
+                           MinFresh, MustRevalidate, Public, Private, ProxyRevalidate, SMaxAge,
+                           Extension};
+
+/// The `Cache-Control` header.
+///
+/// > The "Cache-Control" header field is used to specify directives for
+/// > caches along the request/response chain.
+///
+/// The field may be sent several times, in which case the directives of
+/// every line are collected.
+#[deriving(Clone, PartialEq, Show)]
+pub struct CacheControl(pub Vec<CacheDirective>);
+
+/// A directive of the `Cache-Control` header.
+#[deriving(Clone, PartialEq)]
+pub enum CacheDirective {
+    /// "no-cache"
+    NoCache,
+    /// "no-store"
+    NoStore,
+    /// "no-transform"
+    NoTransform,
+    /// "only-if-cached"
+    OnlyIfCached,
+
+    /// "max-age=delta", in seconds
+    MaxAge(u32),
+    /// "max-stale" or "max-stale=delta", in seconds
+    MaxStale(Option<u32>),
+    /// "min-fresh=delta", in seconds
+    MinFresh(u32),
+
+    /// "must-revalidate"
+    MustRevalidate,
+    /// "public"
+    Public,
+    /// "private"
+    Private,
+    /// "proxy-revalidate"
+    ProxyRevalidate,
+    /// "s-maxage=delta", in seconds
+    SMaxAge(u32),
+
+    /// Any other directive, with its argument if it has one.
+    Extension(String, Option<String>),
+}
+
+impl FromStr for CacheDirective {
+    fn from_str(s: &str) -> Option<CacheDirective> {
+        let s = s.trim();
+        if s.is_empty() {
+            return None;
+        }
+        let (name, arg) = match s.find('=') {
+            Some(i) => (s[..i].trim(), Some(s[i + 1..].trim().trim_chars('"'))),
+            None => (s, None)
+        };
+        let secs = arg.and_then(|arg| from_str::<u32>(arg));
+        Some(match (name.to_ascii_lower()[], arg, secs) {
+            ("no-cache", None, _) => NoCache,
+            ("no-store", None, _) => NoStore,
+            ("no-transform", None, _) => NoTransform,
+            ("only-if-cached", None, _) => OnlyIfCached,
+            ("must-revalidate", None, _) => MustRevalidate,
+            ("public", None, _) => Public,
+            ("private", None, _) => Private,
+            ("proxy-revalidate", None, _) => ProxyRevalidate,
+            ("max-age", _, Some(secs)) => MaxAge(secs),
+            ("max-stale", None, _) => MaxStale(None),
+            ("max-stale", _, Some(secs)) => MaxStale(Some(secs)),
+            ("min-fresh", _, Some(secs)) => MinFresh(secs),
+            ("s-maxage", _, Some(secs)) => SMaxAge(secs),
+            (_, arg, _) => Extension(name.into_string(), arg.map(|arg| arg.into_string()))
+        })
+    }
+}
+
+impl fmt::Show for CacheDirective {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            NoCache => "no-cache".fmt(fmt),
+            NoStore => "no-store".fmt(fmt),
+            NoTransform => "no-transform".fmt(fmt),
+            OnlyIfCached => "only-if-cached".fmt(fmt),
+            MaxAge(secs) => write!(fmt, "max-age={}", secs),
+            MaxStale(None) => "max-stale".fmt(fmt),
+            MaxStale(Some(secs)) => write!(fmt, "max-stale={}", secs),
+            MinFresh(secs) => write!(fmt, "min-fresh={}", secs),
+            MustRevalidate => "must-revalidate".fmt(fmt),
+            Public => "public".fmt(fmt),
+            Private => "private".fmt(fmt),
+            ProxyRevalidate => "proxy-revalidate".fmt(fmt),
+            SMaxAge(secs) => write!(fmt, "s-maxage={}", secs),
+            Extension(ref name, None) => name.fmt(fmt),
+            Extension(ref name, Some(ref arg)) => write!(fmt, "{}={}", name, arg),
+        }
+    }
+}
+
+impl Header for CacheControl {
+    fn header_name(_: Option<CacheControl>) -> &'static str {
+        "Cache-Control"
+    }
+
+    fn parse_header(raw: &[Vec<u8>]) -> Option<CacheControl> {
+        let mut directives = vec![];
+        for line in raw.iter() {
+            let line = match from_utf8(line[]) {
+                Some(line) => line,
+                None => return None
+            };
+            directives.extend(line.split(',').filter_map(from_str::<CacheDirective>));
+        }
+        if directives.is_empty() { None } else { Some(CacheControl(directives)) }
+    }
+}
+
+impl HeaderFormat for CacheControl {
+    fn fmt_header(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt_comma_delimited(fmt, self.0[])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use header::Header;
+    use super::CacheControl;
+    use super::CacheDirective::{NoCache, MaxAge, MaxStale, Private, Extension};
+
+    #[test]
+    fn test_parse() {
+        let cc: CacheControl = Header::parse_header([
+            b"no-cache, max-age=60".to_vec(),
+            b"private, max-stale, community=\"UCI\"".to_vec(),
+        ][]).unwrap();
+        assert_eq!(cc, CacheControl(vec![
+            NoCache, MaxAge(60), Private, MaxStale(None),
+            Extension("community".into_string(), Some("UCI".into_string()))
+        ]));
+    }
+
+    #[test]
+    fn test_parse_bad_age() {
+        let cc: CacheControl = Header::parse_header([b"max-age=soon".to_vec()][]).unwrap();
+        assert_eq!(cc, CacheControl(vec![
+            Extension("max-age".into_string(), Some("soon".into_string()))
+        ]));
+    }
+}
+
+bench_header!(normal, CacheControl, { vec![b"no-cache, private, max-age=0".to_vec()] })
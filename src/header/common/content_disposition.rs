@@ -0,0 +1,141 @@
+use header::{Header, HeaderFormat};
+use std::ascii::AsciiExt;
+use std::fmt::{mod, Show};
+use std::str::FromStr;
+use super::util::{HeaderParam, from_one_raw_str, parse_semicolon_params, fmt_quoted};
+
+pub use self::DispositionType::{Inline, Attachment, FormData, DispositionExt};
+
+/// The `Content-Disposition` header.
+///
+/// Describes how a body should be presented, such as an attachment with a
+/// file name, or which field of a `multipart/form-data` body a part is.
+#[deriving(Clone, PartialEq, Show)]
+pub struct ContentDisposition {
+    /// The disposition type.
+    pub disposition: DispositionType,
+    /// The parameters, such as `filename`.
+    pub params: Vec<HeaderParam>,
+}
+
+/// The types of dispositions.
+#[deriving(Clone, PartialEq)]
+pub enum DispositionType {
+    /// Displayed as part of a message.
+    Inline,
+    /// Saved rather than displayed.
+    Attachment,
+    /// A field of a `multipart/form-data` body.
+    FormData,
+    /// Any other disposition type.
+    DispositionExt(String),
+}
+
+impl ContentDisposition {
+    /// The `form-data` disposition of the field `name`.
+    pub fn form_data(name: &str) -> ContentDisposition {
+        ContentDisposition {
+            disposition: FormData,
+            params: vec![("name".into_string(), name.into_string())],
+        }
+    }
+
+    /// The value of the parameter `name`, ignoring the case of the name.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.iter()
+            .find(|&&(ref n, _)| n[].eq_ignore_ascii_case(name))
+            .map(|&(_, ref value)| value[])
+    }
+}
+
+impl FromStr for DispositionType {
+    fn from_str(s: &str) -> Option<DispositionType> {
+        Some(match s.to_ascii_lower()[] {
+            "inline" => Inline,
+            "attachment" => Attachment,
+            "form-data" => FormData,
+            _ => DispositionExt(s.into_string())
+        })
+    }
+}
+
+impl fmt::Show for DispositionType {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Inline => "inline",
+            Attachment => "attachment",
+            FormData => "form-data",
+            DispositionExt(ref s) => s[]
+        }.fmt(fmt)
+    }
+}
+
+impl FromStr for ContentDisposition {
+    fn from_str(s: &str) -> Option<ContentDisposition> {
+        let (disposition, rest) = match s.find(';') {
+            Some(i) => (s[..i], s[i + 1..]),
+            None => (s, "")
+        };
+        let disposition = disposition.trim();
+        if disposition.is_empty() {
+            return None;
+        }
+        match (from_str(disposition), parse_semicolon_params(rest)) {
+            (Some(disposition), Some(params)) => Some(ContentDisposition {
+                disposition: disposition,
+                params: params,
+            }),
+            _ => None
+        }
+    }
+}
+
+impl Header for ContentDisposition {
+    fn header_name(_: Option<ContentDisposition>) -> &'static str {
+        "Content-Disposition"
+    }
+
+    fn parse_header(raw: &[Vec<u8>]) -> Option<ContentDisposition> {
+        from_one_raw_str(raw)
+    }
+}
+
+impl HeaderFormat for ContentDisposition {
+    fn fmt_header(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        try!(self.disposition.fmt(fmt));
+        for &(ref name, ref value) in self.params.iter() {
+            try!(write!(fmt, "; {}=", name));
+            try!(fmt_quoted(fmt, value[]));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use header::Headers;
+    use super::{ContentDisposition, Attachment};
+
+    #[test]
+    fn test_form_data() {
+        let mut disposition = ContentDisposition::form_data("upload");
+        disposition.params.push(("filename".into_string(), "a; \"b\".txt".into_string()));
+        let mut headers = Headers::new();
+        headers.set(disposition.clone());
+        assert_eq!(headers.to_string()[],
+                   "Content-Disposition: form-data; name=\"upload\"; filename=\"a; \\\"b\\\".txt\"\r\n");
+
+        let parsed: ContentDisposition = from_str(
+            "form-data; name=\"upload\"; filename=\"a; \\\"b\\\".txt\"").unwrap();
+        assert_eq!(parsed, disposition);
+    }
+
+    #[test]
+    fn test_attachment() {
+        let parsed: ContentDisposition = from_str("Attachment; filename=report.pdf").unwrap();
+        assert_eq!(parsed.disposition, Attachment);
+        assert_eq!(parsed.param("FileName"), Some("report.pdf"));
+    }
+}
+
+bench_header!(bench, ContentDisposition, { vec![b"form-data; name=\"upload\"; filename=\"a.txt\"".to_vec()] })
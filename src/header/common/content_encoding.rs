@@ -0,0 +1,37 @@
+use header::{Header, HeaderFormat};
+use std::fmt;
+use super::util::{from_comma_delimited, fmt_comma_delimited};
+use super::transfer_encoding::Encoding;
+
+/// The `Content-Encoding` header.
+///
+/// This header describes the codings applied to the body of a message,
+/// in the order they were applied.
+///
+/// ```notrust
+/// Content-Encoding: gzip
+/// ```
+///
+/// The implementation uses a vector of `Encoding` values, the same as
+/// `TransferEncoding`.
+#[deriving(Clone, PartialEq, Show)]
+pub struct ContentEncoding(pub Vec<Encoding>);
+
+impl Header for ContentEncoding {
+    fn header_name(_: Option<ContentEncoding>) -> &'static str {
+        "Content-Encoding"
+    }
+
+    fn parse_header(raw: &[Vec<u8>]) -> Option<ContentEncoding> {
+        from_comma_delimited(raw).map(|vec| ContentEncoding(vec))
+    }
+}
+
+impl HeaderFormat for ContentEncoding {
+    fn fmt_header(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let ContentEncoding(ref parts) = *self;
+        fmt_comma_delimited(fmt, parts[])
+    }
+}
+
+bench_header!(bench, ContentEncoding, { vec![b"gzip".to_vec()] })
@@ -0,0 +1,112 @@
+use std::fmt::{mod, Show};
+use std::str::FromStr;
+
+use header::{Header, HeaderFormat};
+use super::util::from_one_raw_str;
+
+/// The `Content-Range` header.
+///
+/// > The "Content-Range" header field is sent in a single part 206
+/// > (Partial Content) response to indicate the partial range of the
+/// > selected representation enclosed as the message payload, sent in
+/// > each part of a multipart 206 response to indicate the range enclosed
+/// > within each body part, and sent in 416 (Range Not Satisfiable)
+/// > responses to provide information about the selected representation.
+///
+/// Only ranges of `bytes` are understood.
+#[deriving(Clone, PartialEq, Show)]
+pub struct ContentRange {
+    /// The first and last offsets of the enclosed bytes, both included, or
+    /// `None` for `*` in a 416.
+    pub range: Option<(u64, u64)>,
+    /// The length of the whole representation, if the server knows it.
+    pub complete_length: Option<u64>,
+}
+
+impl FromStr for ContentRange {
+    fn from_str(s: &str) -> Option<ContentRange> {
+        let s = s.trim();
+        if !s.starts_with("bytes ") {
+            return None;
+        }
+        let s = s[6..].trim();
+        let slash = match s.find('/') {
+            Some(slash) => slash,
+            None => return None
+        };
+        let (range, length) = (s[..slash].trim(), s[slash + 1..].trim());
+
+        let complete_length = match length {
+            "*" => None,
+            length => match from_str::<u64>(length) {
+                Some(length) => Some(length),
+                None => return None
+            }
+        };
+        let range = match range {
+            "*" if complete_length.is_some() => None,
+            range => {
+                let dash = match range.find('-') {
+                    Some(dash) => dash,
+                    None => return None
+                };
+                match (from_str::<u64>(range[..dash]), from_str::<u64>(range[dash + 1..])) {
+                    (Some(first), Some(last)) if first <= last &&
+                            complete_length.map_or(true, |len| last < len) => {
+                        Some((first, last))
+                    },
+                    _ => return None
+                }
+            }
+        };
+        Some(ContentRange { range: range, complete_length: complete_length })
+    }
+}
+
+impl Header for ContentRange {
+    fn header_name(_: Option<ContentRange>) -> &'static str {
+        "Content-Range"
+    }
+
+    fn parse_header(raw: &[Vec<u8>]) -> Option<ContentRange> {
+        from_one_raw_str(raw)
+    }
+}
+
+impl HeaderFormat for ContentRange {
+    fn fmt_header(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        try!("bytes ".fmt(fmt));
+        match self.range {
+            Some((first, last)) => try!(write!(fmt, "{}-{}", first, last)),
+            None => try!("*".fmt(fmt))
+        }
+        match self.complete_length {
+            Some(len) => write!(fmt, "/{}", len),
+            None => "/*".fmt(fmt)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use header::Header;
+    use super::ContentRange;
+
+    fn parse(s: &str) -> Option<ContentRange> {
+        Header::parse_header([s.as_bytes().to_vec()][])
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("bytes 42-1233/1234"),
+                   Some(ContentRange { range: Some((42, 1233)), complete_length: Some(1234) }));
+        assert_eq!(parse("bytes 42-1233/*"),
+                   Some(ContentRange { range: Some((42, 1233)), complete_length: None }));
+        assert_eq!(parse("bytes */1234"),
+                   Some(ContentRange { range: None, complete_length: Some(1234) }));
+        assert_eq!(parse("bytes 42-1234/1234"), None);
+        assert_eq!(parse("bytes */*"), None);
+    }
+}
+
+bench_header!(bench, ContentRange, { vec![b"bytes 21010-47021/47022".to_vec()] })
@@ -0,0 +1,64 @@
+use std::fmt::{mod, Show};
+
+use header::{Header, HeaderFormat};
+use super::util::from_one_raw_str;
+
+/// The `ETag` header.
+///
+/// > The "ETag" header field in a response provides the current entity-tag
+/// > for the selected representation, as determined at the conclusion of
+/// > handling the request.
+///
+/// The entity-tag is kept as it was sent, quotes and weakness indicator
+/// included, such as `W/"xyzzy"`, so it can be sent back in an
+/// `If-None-Match` as it is.
+#[deriving(Clone, PartialEq, Show)]
+pub struct ETag(pub String);
+
+impl ETag {
+    /// Whether this is a weak entity-tag, starting with `W/`.
+    pub fn is_weak(&self) -> bool {
+        self.0[].starts_with("W/")
+    }
+}
+
+impl Header for ETag {
+    fn header_name(_: Option<ETag>) -> &'static str {
+        "ETag"
+    }
+
+    fn parse_header(raw: &[Vec<u8>]) -> Option<ETag> {
+        from_one_raw_str::<String>(raw).and_then(|tag| {
+            let tag = tag[].trim();
+            if tag.ends_with("\"") && (tag.starts_with("\"") || tag.starts_with("W/\"")) &&
+                    tag.len() >= 2 {
+                Some(ETag(tag.into_string()))
+            } else {
+                None
+            }
+        })
+    }
+}
+
+impl HeaderFormat for ETag {
+    fn fmt_header(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(fmt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use header::Header;
+    use super::ETag;
+
+    #[test]
+    fn test_parse() {
+        let tag: ETag = Header::parse_header([b"W/\"xyzzy\"".to_vec()][]).unwrap();
+        assert_eq!(tag, ETag("W/\"xyzzy\"".into_string()));
+        assert!(tag.is_weak());
+        let tag: Option<ETag> = Header::parse_header([b"xyzzy".to_vec()][]);
+        assert_eq!(tag, None);
+    }
+}
+
+bench_header!(bench, ETag, { vec![b"\"33a64df551425fcc55e4d42a148795d9f25f89d4\"".to_vec()] })
@@ -0,0 +1,49 @@
+use header::{Header, HeaderFormat};
+use std::ascii::AsciiExt;
+use std::fmt::{mod, Show};
+use std::str::FromStr;
+use super::util::from_one_raw_str;
+
+pub use self::Expect::Continue;
+
+/// The `Expect` header.
+///
+/// > The "Expect" header field in a request indicates a certain set of
+/// > behaviors (expectations) that need to be supported by the server in
+/// > order to properly handle this request. The only such expectation
+/// > defined by this specification is 100-continue.
+#[deriving(Clone, PartialEq, Show)]
+pub enum Expect {
+    /// The `100-continue` expectation.
+    Continue,
+}
+
+impl FromStr for Expect {
+    fn from_str(s: &str) -> Option<Expect> {
+        if s.eq_ignore_ascii_case("100-continue") {
+            Some(Continue)
+        } else {
+            None
+        }
+    }
+}
+
+impl Header for Expect {
+    fn header_name(_: Option<Expect>) -> &'static str {
+        "Expect"
+    }
+
+    fn parse_header(raw: &[Vec<u8>]) -> Option<Expect> {
+        from_one_raw_str(raw)
+    }
+}
+
+impl HeaderFormat for Expect {
+    fn fmt_header(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Continue => "100-continue".fmt(fmt)
+        }
+    }
+}
+
+bench_header!(bench, Expect, { vec![b"100-continue".to_vec()] })
@@ -0,0 +1,33 @@
+use header::{Header, HeaderFormat};
+use std::fmt::{mod, Show};
+use time::Tm;
+use super::Date;
+use super::util::from_one_raw_str;
+
+/// The `Expires` header.
+///
+/// > The "Expires" header field gives the date/time after which the
+/// > response is considered stale.
+///
+/// An invalid date, such as `0`, fails to parse, and is meant to be taken
+/// as a time in the past.
+#[deriving(PartialEq, Clone)]
+pub struct Expires(pub Tm);
+
+impl Header for Expires {
+    fn header_name(_: Option<Expires>) -> &'static str {
+        "Expires"
+    }
+
+    fn parse_header(raw: &[Vec<u8>]) -> Option<Expires> {
+        from_one_raw_str(raw).map(|Date(tm)| Expires(tm))
+    }
+}
+
+impl HeaderFormat for Expires {
+    fn fmt_header(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        Date(self.0).fmt(fmt)
+    }
+}
+
+bench_header!(imf_fixdate, Expires, { vec![b"Sun, 07 Nov 1994 08:48:37 GMT".to_vec()] })
@@ -0,0 +1,31 @@
+use header::{Header, HeaderFormat};
+use std::fmt::{mod, Show};
+use time::Tm;
+use super::Date;
+use super::util::from_one_raw_str;
+
+/// The `If-Modified-Since` header.
+///
+/// > The "If-Modified-Since" header field makes a GET or HEAD request
+/// > method conditional on the selected representation's modification date
+/// > being more recent than the date provided in the field-value.
+#[deriving(PartialEq, Clone)]
+pub struct IfModifiedSince(pub Tm);
+
+impl Header for IfModifiedSince {
+    fn header_name(_: Option<IfModifiedSince>) -> &'static str {
+        "If-Modified-Since"
+    }
+
+    fn parse_header(raw: &[Vec<u8>]) -> Option<IfModifiedSince> {
+        from_one_raw_str(raw).map(|Date(tm)| IfModifiedSince(tm))
+    }
+}
+
+impl HeaderFormat for IfModifiedSince {
+    fn fmt_header(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        Date(self.0).fmt(fmt)
+    }
+}
+
+bench_header!(imf_fixdate, IfModifiedSince, { vec![b"Sun, 07 Nov 1994 08:48:37 GMT".to_vec()] })
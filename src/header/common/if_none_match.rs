@@ -0,0 +1,56 @@
+use std::fmt::{mod, Show};
+use std::str::from_utf8;
+
+use header::{Header, HeaderFormat};
+use super::util::fmt_comma_delimited;
+
+/// The `If-None-Match` header.
+///
+/// > The "If-None-Match" header field makes the request method conditional
+/// > on a recipient cache or origin server either not having any current
+/// > representation of the target resource, when the field-value is "*",
+/// > or having a selected representation with an entity-tag that does not
+/// > match any of those listed in the field-value.
+///
+/// Entity-tags are kept as they are sent, such as `"xyzzy"`, like `ETag`.
+#[deriving(Clone, PartialEq, Show)]
+pub enum IfNoneMatch {
+    /// `*`, any current representation.
+    Any,
+    /// A list of entity-tags.
+    EntityTags(Vec<String>),
+}
+
+impl Header for IfNoneMatch {
+    fn header_name(_: Option<IfNoneMatch>) -> &'static str {
+        "If-None-Match"
+    }
+
+    fn parse_header(raw: &[Vec<u8>]) -> Option<IfNoneMatch> {
+        let mut tags = vec![];
+        for line in raw.iter() {
+            let line = match from_utf8(line[]) {
+                Some(line) => line,
+                None => return None
+            };
+            for tag in line.split(',').map(|tag| tag.trim()).filter(|t| !t.is_empty()) {
+                if tag == "*" {
+                    return Some(IfNoneMatch::Any);
+                }
+                tags.push(tag.into_string());
+            }
+        }
+        if tags.is_empty() { None } else { Some(IfNoneMatch::EntityTags(tags)) }
+    }
+}
+
+impl HeaderFormat for IfNoneMatch {
+    fn fmt_header(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            IfNoneMatch::Any => "*".fmt(fmt),
+            IfNoneMatch::EntityTags(ref tags) => fmt_comma_delimited(fmt, tags[])
+        }
+    }
+}
+
+bench_header!(bench, IfNoneMatch, { vec![b"\"xyzzy\", W/\"r2d2xxxx\"".to_vec()] })
@@ -0,0 +1,64 @@
+use std::fmt::{mod, Show};
+use std::str::FromStr;
+use time::Tm;
+
+use header::{Header, HeaderFormat};
+use super::Date;
+use super::util::from_one_raw_str;
+
+/// The `If-Range` header.
+///
+/// > If a client has a partial copy of a representation and wishes to have
+/// > an up-to-date copy of the entire representation, it could use the
+/// > Range header field with a conditional GET (using either or both of
+/// > If-Unmodified-Since and If-Match.)  However, if the precondition
+/// > fails because the representation has been modified, the client would
+/// > then have to make a second request to obtain the entire current
+/// > representation.
+/// >
+/// > The "If-Range" header field allows a client to "short-circuit" the
+/// > second request.
+#[deriving(Clone, PartialEq)]
+pub enum IfRange {
+    /// A strong entity-tag, quoted as in an `ETag`.
+    EntityTag(String),
+    /// A `Last-Modified` date.
+    Date(Tm),
+}
+
+impl FromStr for IfRange {
+    fn from_str(s: &str) -> Option<IfRange> {
+        let s = s.trim();
+        if s.starts_with("\"") {
+            if s.len() >= 2 && s.ends_with("\"") {
+                Some(IfRange::EntityTag(s.into_string()))
+            } else {
+                None
+            }
+        } else {
+            from_str::<Date>(s).map(|Date(tm)| IfRange::Date(tm))
+        }
+    }
+}
+
+impl Header for IfRange {
+    fn header_name(_: Option<IfRange>) -> &'static str {
+        "If-Range"
+    }
+
+    fn parse_header(raw: &[Vec<u8>]) -> Option<IfRange> {
+        from_one_raw_str(raw)
+    }
+}
+
+impl HeaderFormat for IfRange {
+    fn fmt_header(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            IfRange::EntityTag(ref tag) => tag.fmt(fmt),
+            IfRange::Date(tm) => Date(tm).fmt(fmt)
+        }
+    }
+}
+
+bench_header!(bench_tag, IfRange, { vec![b"\"xyzzy\"".to_vec()] })
+bench_header!(bench_date, IfRange, { vec![b"Sun, 07 Nov 1994 08:48:37 GMT".to_vec()] })
@@ -0,0 +1,31 @@
+use header::{Header, HeaderFormat};
+use std::fmt::{mod, Show};
+use time::Tm;
+use super::Date;
+use super::util::from_one_raw_str;
+
+/// The `Last-Modified` header.
+///
+/// > The "Last-Modified" header field in a response provides a timestamp
+/// > indicating the date and time at which the origin server believes the
+/// > selected representation was last modified.
+#[deriving(PartialEq, Clone)]
+pub struct LastModified(pub Tm);
+
+impl Header for LastModified {
+    fn header_name(_: Option<LastModified>) -> &'static str {
+        "Last-Modified"
+    }
+
+    fn parse_header(raw: &[Vec<u8>]) -> Option<LastModified> {
+        from_one_raw_str(raw).map(|Date(tm)| LastModified(tm))
+    }
+}
+
+impl HeaderFormat for LastModified {
+    fn fmt_header(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        Date(self.0).fmt(fmt)
+    }
+}
+
+bench_header!(imf_fixdate, LastModified, { vec![b"Sun, 07 Nov 1994 08:48:37 GMT".to_vec()] })
@@ -7,17 +7,36 @@
 //! is used, such as `ContentType(pub Mime)`.
 
 pub use self::accept::Accept;
+pub use self::accept_encoding::AcceptEncoding;
+pub use self::age::Age;
 pub use self::authorization::Authorization;
+pub use self::cache_control::CacheControl;
 pub use self::cookie::Cookies;
 pub use self::connection::Connection;
+pub use self::content_disposition::ContentDisposition;
+pub use self::content_encoding::ContentEncoding;
 pub use self::content_length::ContentLength;
+pub use self::content_range::ContentRange;
 pub use self::content_type::ContentType;
 pub use self::date::Date;
+pub use self::etag::ETag;
+pub use self::expect::Expect;
+pub use self::expires::Expires;
 pub use self::host::Host;
+pub use self::if_modified_since::IfModifiedSince;
+pub use self::if_none_match::IfNoneMatch;
+pub use self::if_range::IfRange;
+pub use self::last_modified::LastModified;
 pub use self::location::Location;
+pub use self::proxy_authenticate::ProxyAuthenticate;
+pub use self::proxy_authorization::ProxyAuthorization;
+pub use self::range::Range;
+pub use self::retry_after::RetryAfter;
 pub use self::transfer_encoding::TransferEncoding;
 pub use self::upgrade::Upgrade;
 pub use self::user_agent::UserAgent;
+pub use self::vary::Vary;
+pub use self::www_authenticate::WwwAuthenticate;
 pub use self::server::Server;
 pub use self::set_cookie::SetCookie;
 
@@ -62,30 +81,81 @@ macro_rules! bench_header(
 /// Exposes the Accept header.
 pub mod accept;
 
+/// Exposes the AcceptEncoding header.
+pub mod accept_encoding;
+
+/// Exposes the Age header.
+pub mod age;
+
 /// Exposes the Authorization header.
 pub mod authorization;
 
+/// Exposes the Cache-Control header.
+pub mod cache_control;
+
 /// Exposes the Cookie header.
 pub mod cookie;
 
 /// Exposes the Connection header.
 pub mod connection;
 
+/// Exposes the Content-Disposition header.
+pub mod content_disposition;
+
+/// Exposes the ContentEncoding header.
+pub mod content_encoding;
+
 /// Exposes the ContentLength header.
 pub mod content_length;
 
+/// Exposes the Content-Range header.
+pub mod content_range;
+
 /// Exposes the ContentType header.
 pub mod content_type;
 
 /// Exposes the Date header.
 pub mod date;
 
+/// Exposes the ETag header.
+pub mod etag;
+
+/// Exposes the Expect header.
+pub mod expect;
+
+/// Exposes the Expires header.
+pub mod expires;
+
 /// Exposes the Host header.
 pub mod host;
 
+/// Exposes the If-Modified-Since header.
+pub mod if_modified_since;
+
+/// Exposes the If-None-Match header.
+pub mod if_none_match;
+
+/// Exposes the If-Range header.
+pub mod if_range;
+
+/// Exposes the Last-Modified header.
+pub mod last_modified;
+
 /// Exposes the Location header.
 pub mod location;
 
+/// Exposes the Proxy-Authenticate header.
+pub mod proxy_authenticate;
+
+/// Exposes the Proxy-Authorization header.
+pub mod proxy_authorization;
+
+/// Exposes the Range header.
+pub mod range;
+
+/// Exposes the Retry-After header.
+pub mod retry_after;
+
 /// Exposes the Server header.
 pub mod server;
 
@@ -101,4 +171,10 @@ pub mod upgrade;
 /// Exposes the UserAgent header.
 pub mod user_agent;
 
+/// Exposes the Vary header.
+pub mod vary;
+
+/// Exposes the WWW-Authenticate header.
+pub mod www_authenticate;
+
 pub mod util;
@@ -0,0 +1,29 @@
+use std::fmt;
+
+use header::{Header, HeaderFormat};
+use super::www_authenticate::{Challenge, challenges_from_raw, fmt_challenges};
+
+/// The `Proxy-Authenticate` header field.
+///
+/// Sent with a `407 Proxy Authentication Required` response, it lists the
+/// challenges of a proxy, answered with a `Proxy-Authorization` header.
+#[deriving(Clone, PartialEq, Show)]
+pub struct ProxyAuthenticate(pub Vec<Challenge>);
+
+impl Header for ProxyAuthenticate {
+    fn header_name(_: Option<ProxyAuthenticate>) -> &'static str {
+        "Proxy-Authenticate"
+    }
+
+    fn parse_header(raw: &[Vec<u8>]) -> Option<ProxyAuthenticate> {
+        challenges_from_raw(raw).map(|c| ProxyAuthenticate(c))
+    }
+}
+
+impl HeaderFormat for ProxyAuthenticate {
+    fn fmt_header(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt_challenges(fmt, self.0[])
+    }
+}
+
+bench_header!(bench, ProxyAuthenticate, { vec![b"Basic realm=\"proxy\"".to_vec()] })
@@ -0,0 +1,109 @@
+use std::fmt::{mod, Show};
+use std::str::FromStr;
+
+use header::{Header, HeaderFormat};
+use super::util::{from_one_raw_str, fmt_comma_delimited};
+
+use self::ByteRange::{FromTo, AllFrom, Last};
+
+/// The `Range` header.
+///
+/// > The "Range" header field on a GET request modifies the method
+/// > semantics to request transfer of only one or more subranges of the
+/// > selected representation data, rather than the entire selected
+/// > representation data.
+///
+/// Only ranges of `bytes` are understood.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Range(pub Vec<ByteRange>);
+
+/// A range of bytes, as requested in a `Range` header.
+#[deriving(Clone, PartialEq)]
+pub enum ByteRange {
+    /// The bytes from the first offset to the last one, both included.
+    FromTo(u64, u64),
+    /// The bytes from an offset to the end.
+    AllFrom(u64),
+    /// The last bytes, this many of them.
+    Last(u64),
+}
+
+impl FromStr for ByteRange {
+    fn from_str(s: &str) -> Option<ByteRange> {
+        let s = s.trim();
+        let dash = match s.find('-') {
+            Some(dash) => dash,
+            None => return None
+        };
+        let (first, last) = (s[..dash].trim(), s[dash + 1..].trim());
+        match (from_str::<u64>(first), from_str::<u64>(last)) {
+            (Some(first), Some(last)) if first <= last => Some(FromTo(first, last)),
+            (Some(first), None) if last.is_empty() => Some(AllFrom(first)),
+            (None, Some(last)) if first.is_empty() => Some(Last(last)),
+            _ => None
+        }
+    }
+}
+
+impl fmt::Show for ByteRange {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FromTo(first, last) => write!(fmt, "{}-{}", first, last),
+            AllFrom(first) => write!(fmt, "{}-", first),
+            Last(len) => write!(fmt, "-{}", len),
+        }
+    }
+}
+
+impl FromStr for Range {
+    fn from_str(s: &str) -> Option<Range> {
+        let s = s.trim();
+        if !s.starts_with("bytes=") {
+            return None;
+        }
+        let mut ranges = vec![];
+        for range in s[6..].split(',').filter(|r| !r.trim().is_empty()) {
+            match from_str(range) {
+                Some(range) => ranges.push(range),
+                None => return None
+            }
+        }
+        if ranges.is_empty() { None } else { Some(Range(ranges)) }
+    }
+}
+
+impl Header for Range {
+    fn header_name(_: Option<Range>) -> &'static str {
+        "Range"
+    }
+
+    fn parse_header(raw: &[Vec<u8>]) -> Option<Range> {
+        from_one_raw_str(raw)
+    }
+}
+
+impl HeaderFormat for Range {
+    fn fmt_header(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        try!("bytes=".fmt(fmt));
+        fmt_comma_delimited(fmt, self.0[])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use header::Header;
+    use super::Range;
+    use super::ByteRange::{FromTo, AllFrom, Last};
+
+    #[test]
+    fn test_parse() {
+        let range: Range = Header::parse_header([b"bytes=0-499, 1000-, -200".to_vec()][]).unwrap();
+        assert_eq!(range, Range(vec![FromTo(0, 499), AllFrom(1000), Last(200)]));
+        let range: Option<Range> = Header::parse_header([b"bytes=500-100".to_vec()][]);
+        assert_eq!(range, None);
+        let range: Option<Range> = Header::parse_header([b"lines=1-2".to_vec()][]);
+        assert_eq!(range, None);
+    }
+}
+
+bench_header!(bench, Range, { vec![b"bytes=0-499, 1000-".to_vec()] })
@@ -0,0 +1,60 @@
+use header::{Header, HeaderFormat};
+use std::fmt::{mod, Show};
+use std::str::FromStr;
+use std::time::Duration;
+use time::Tm;
+use super::Date;
+use super::util::from_one_raw_str;
+
+pub use self::RetryAfter::{Delay, DateTime};
+
+/// The `Retry-After` header.
+///
+/// > Servers send the "Retry-After" header field to indicate how long the
+/// > user agent ought to wait before making a follow-up request.
+///
+/// Its value is either a number of seconds, or an HTTP-date.
+#[deriving(Clone, PartialEq)]
+pub enum RetryAfter {
+    /// Retry after this long.
+    Delay(Duration),
+    /// Retry after this point in time.
+    DateTime(Tm),
+}
+
+impl FromStr for RetryAfter {
+    fn from_str(s: &str) -> Option<RetryAfter> {
+        match from_str::<u32>(s.trim()) {
+            Some(secs) => Some(Delay(Duration::seconds(secs as i64))),
+            None => from_str::<Date>(s).map(|Date(tm)| DateTime(tm))
+        }
+    }
+}
+
+impl Header for RetryAfter {
+    fn header_name(_: Option<RetryAfter>) -> &'static str {
+        "Retry-After"
+    }
+
+    fn parse_header(raw: &[Vec<u8>]) -> Option<RetryAfter> {
+        from_one_raw_str(raw)
+    }
+}
+
+impl HeaderFormat for RetryAfter {
+    fn fmt_header(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt(fmt)
+    }
+}
+
+impl fmt::Show for RetryAfter {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Delay(ref delay) => delay.num_seconds().fmt(fmt),
+            DateTime(ref tm) => Date(*tm).fmt(fmt)
+        }
+    }
+}
+
+bench_header!(bench_delay, RetryAfter, { vec![b"120".to_vec()] })
+bench_header!(bench_date, RetryAfter, { vec![b"Sun, 07 Nov 1994 08:48:37 GMT".to_vec()] })
@@ -1,4 +1,5 @@
 use header::{Header, HeaderFormat};
+use std::ascii::AsciiExt;
 use std::fmt::{mod, Show};
 use std::str::FromStr;
 use super::util::{from_comma_delimited, fmt_comma_delimited};
@@ -7,7 +8,7 @@ use self::Protocol::{WebSocket, ProtocolExt};
 
 /// The `Upgrade` header.
 #[deriving(Clone, PartialEq, Show)]
-pub struct Upgrade(Vec<Protocol>);
+pub struct Upgrade(pub Vec<Protocol>);
 
 /// Protocol values that can appear in the Upgrade header.
 #[deriving(Clone, PartialEq)]
@@ -20,9 +21,11 @@ pub enum Protocol {
 
 impl FromStr for Protocol {
     fn from_str(s: &str) -> Option<Protocol> {
-        match s {
-            "websocket" => Some(WebSocket),
-            s => Some(ProtocolExt(s.to_string()))
+        // protocol names are case-insensitive
+        if s.to_ascii_lower()[] == "websocket" {
+            Some(WebSocket)
+        } else {
+            Some(ProtocolExt(s.to_string()))
         }
     }
 }
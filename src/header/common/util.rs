@@ -3,6 +3,8 @@
 use std::str::{FromStr, from_utf8};
 use std::fmt::{mod, Show};
 
+use http::is_token;
+
 /// Reads a single raw string when parsing a header
 pub fn from_one_raw_str<T: FromStr>(raw: &[Vec<u8>]) -> Option<T> {
     if raw.len() != 1 {
@@ -15,6 +17,15 @@ pub fn from_one_raw_str<T: FromStr>(raw: &[Vec<u8>]) -> Option<T> {
     }
 }
 
+/// Reads a single raw string as a trimmed String, for headers without a
+/// typed representation.
+pub fn one_raw_string(raw: &[Vec<u8>]) -> Option<String> {
+    if raw.len() != 1 {
+        return None;
+    }
+    from_utf8(raw[0][]).map(|s| s.trim().into_string())
+}
+
 /// Reads a comma-delimited raw string into a Vec.
 pub fn from_comma_delimited<T: FromStr>(raw: &[Vec<u8>]) -> Option<Vec<T>> {
     if raw.len() != 1 {
@@ -43,3 +54,177 @@ pub fn fmt_comma_delimited<T: Show>(fmt: &mut fmt::Formatter, parts: &[T]) -> fm
     }
     Ok(())
 }
+
+/// A `name=value` parameter of a header, such as `realm="example"`.
+pub type HeaderParam = (String, String);
+
+/// Parses a list of `auth-param`s, as found in credentials such as `Digest`.
+pub fn parse_auth_params(s: &str) -> Option<Vec<HeaderParam>> {
+    parse_params(s, b',')
+}
+
+/// Parses a list of parameters delimited by `;`, such as those following
+/// the type of a `Content-Disposition`.
+pub fn parse_semicolon_params(s: &str) -> Option<Vec<HeaderParam>> {
+    parse_params(s, b';')
+}
+
+fn parse_params(s: &str, sep: u8) -> Option<Vec<HeaderParam>> {
+    let mut parser = ParamParser { s: s.as_bytes(), pos: 0, sep: sep };
+    let params = parser.params();
+    parser.skip(|b| b == b' ' || b == b'\t' || b == sep);
+    if parser.at_end() { Some(params) } else { None }
+}
+
+/// Parses the challenges of a `WWW-Authenticate` or `Proxy-Authenticate`
+/// header, each a scheme followed by its `auth-param`s.
+///
+/// A `token68` following a scheme is skipped, since neither `Basic` nor
+/// `Digest` use one.
+pub fn parse_challenges(s: &str) -> Option<Vec<(String, Vec<HeaderParam>)>> {
+    let mut parser = ParamParser { s: s.as_bytes(), pos: 0, sep: b',' };
+    let mut challenges = vec![];
+    loop {
+        parser.skip(|b| b == b' ' || b == b'\t' || b == b',');
+        if parser.at_end() {
+            break;
+        }
+        let scheme = match parser.token() {
+            Some(scheme) => scheme,
+            None => return None
+        };
+        parser.skip_token68();
+        challenges.push((scheme, parser.params()));
+    }
+    if challenges.is_empty() { None } else { Some(challenges) }
+}
+
+/// Format `auth-param`s as a comma-delimited list, quoting every value.
+pub fn fmt_auth_params(fmt: &mut fmt::Formatter, params: &[HeaderParam]) -> fmt::Result {
+    for (i, &(ref name, ref value)) in params.iter().enumerate() {
+        if i > 0 {
+            try!(", ".fmt(fmt));
+        }
+        try!(write!(fmt, "{}=", name));
+        try!(fmt_quoted(fmt, value[]));
+    }
+    Ok(())
+}
+
+/// Format `value` as a quoted-string.
+pub fn fmt_quoted(fmt: &mut fmt::Formatter, value: &str) -> fmt::Result {
+    try!("\"".fmt(fmt));
+    for c in value.chars() {
+        if c == '"' || c == '\\' {
+            try!('\\'.fmt(fmt));
+        }
+        try!(c.fmt(fmt));
+    }
+    "\"".fmt(fmt)
+}
+
+struct ParamParser<'a> {
+    s: &'a [u8],
+    pos: uint,
+    sep: u8,
+}
+
+impl<'a> ParamParser<'a> {
+    fn at_end(&self) -> bool {
+        self.pos >= self.s.len()
+    }
+
+    fn peek(&self) -> Option<u8> {
+        if self.at_end() { None } else { Some(self.s[self.pos]) }
+    }
+
+    fn skip(&mut self, f: |u8| -> bool) {
+        while !self.at_end() && f(self.s[self.pos]) {
+            self.pos += 1;
+        }
+    }
+
+    fn token(&mut self) -> Option<String> {
+        let start = self.pos;
+        self.skip(|b| is_token(b));
+        if self.pos == start {
+            None
+        } else {
+            Some(String::from_utf8_lossy(self.s[start..self.pos]).into_string())
+        }
+    }
+
+    fn quoted(&mut self) -> Option<String> {
+        let mut value = vec![];
+        self.pos += 1;
+        loop {
+            match self.peek() {
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Some(String::from_utf8_lossy(value[]).into_string());
+                },
+                Some(b'\\') if self.pos + 1 < self.s.len() => {
+                    value.push(self.s[self.pos + 1]);
+                    self.pos += 2;
+                },
+                Some(b) => {
+                    value.push(b);
+                    self.pos += 1;
+                },
+                None => return None
+            }
+        }
+    }
+
+    // token68 = 1*( ALPHA / DIGIT / "-" / "." / "_" / "~" / "+" / "/" ) *"="
+    fn skip_token68(&mut self) {
+        let start = self.pos;
+        self.skip(|b| b == b' ' || b == b'\t');
+        let chars = self.pos;
+        self.skip(|b| match b {
+            b'a'...b'z' | b'A'...b'Z' | b'0'...b'9' |
+            b'-' | b'.' | b'_' | b'~' | b'+' | b'/' => true,
+            _ => false
+        });
+        let found = self.pos > chars;
+        self.skip(|b| b == b'=');
+        self.skip(|b| b == b' ' || b == b'\t');
+        if !(found && (self.at_end() || self.peek() == Some(b','))) {
+            self.pos = start;
+        }
+    }
+
+    // Reads parameters, and stops before anything else, such as the scheme
+    // of the next challenge.
+    fn params(&mut self) -> Vec<HeaderParam> {
+        let sep = self.sep;
+        let mut params = vec![];
+        loop {
+            self.skip(|b| b == b' ' || b == b'\t' || b == sep);
+            let start = self.pos;
+            let name = match self.token() {
+                Some(name) => name,
+                None => break
+            };
+            self.skip(|b| b == b' ' || b == b'\t');
+            if self.peek() != Some(b'=') {
+                self.pos = start;
+                break;
+            }
+            self.pos += 1;
+            self.skip(|b| b == b' ' || b == b'\t');
+            let value = match self.peek() {
+                Some(b'"') => self.quoted(),
+                _ => self.token()
+            };
+            match value {
+                Some(value) => params.push((name, value)),
+                None => {
+                    self.pos = start;
+                    break;
+                }
+            }
+        }
+        params
+    }
+}
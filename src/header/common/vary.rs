@@ -0,0 +1,68 @@
+use std::fmt::{mod, Show};
+use std::str::from_utf8;
+
+use header::{Header, HeaderFormat};
+use super::util::fmt_comma_delimited;
+
+/// The `Vary` header.
+///
+/// > The "Vary" header field in a response describes what parts of a
+/// > request message, aside from the method, Host header field, and
+/// > request target, might influence the origin server's process for
+/// > selecting and representing this response.
+#[deriving(Clone, PartialEq, Show)]
+pub enum Vary {
+    /// `*`, anything about the request may have mattered.
+    Any,
+    /// The names of the request header fields that mattered.
+    Fields(Vec<String>),
+}
+
+impl Header for Vary {
+    fn header_name(_: Option<Vary>) -> &'static str {
+        "Vary"
+    }
+
+    fn parse_header(raw: &[Vec<u8>]) -> Option<Vary> {
+        let mut fields = vec![];
+        for line in raw.iter() {
+            let line = match from_utf8(line[]) {
+                Some(line) => line,
+                None => return None
+            };
+            for field in line.split(',').map(|field| field.trim()).filter(|f| !f.is_empty()) {
+                if field == "*" {
+                    return Some(Vary::Any);
+                }
+                fields.push(field.into_string());
+            }
+        }
+        Some(Vary::Fields(fields))
+    }
+}
+
+impl HeaderFormat for Vary {
+    fn fmt_header(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Vary::Any => "*".fmt(fmt),
+            Vary::Fields(ref fields) => fmt_comma_delimited(fmt, fields[])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use header::Header;
+    use super::Vary;
+
+    #[test]
+    fn test_parse() {
+        let vary: Vary = Header::parse_header([b"Accept-Encoding,  Accept".to_vec()][]).unwrap();
+        assert_eq!(vary, Vary::Fields(vec!["Accept-Encoding".into_string(),
+                                           "Accept".into_string()]));
+        let vary: Vary = Header::parse_header([b"Accept, *".to_vec()][]).unwrap();
+        assert_eq!(vary, Vary::Any);
+    }
+}
+
+bench_header!(bench, Vary, { vec![b"Accept-Encoding, User-Agent".to_vec()] })
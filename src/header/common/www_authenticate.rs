@@ -0,0 +1,142 @@
+use std::ascii::AsciiExt;
+use std::fmt;
+use std::str::from_utf8;
+
+use header::{Header, HeaderFormat};
+use super::util::{HeaderParam, parse_challenges, fmt_auth_params};
+
+/// The `WWW-Authenticate` header field.
+///
+/// Sent with a `401 Unauthorized` response, it lists the challenges a
+/// client can answer with an `Authorization` header.
+#[deriving(Clone, PartialEq, Show)]
+pub struct WwwAuthenticate(pub Vec<Challenge>);
+
+/// An authentication challenge, such as `Basic realm="example"`.
+#[deriving(Clone, PartialEq)]
+pub struct Challenge {
+    /// The authentication scheme, such as `Basic` or `Digest`.
+    pub scheme: String,
+    /// The parameters of the challenge, in the order they were sent.
+    pub params: Vec<HeaderParam>,
+}
+
+impl Challenge {
+    /// Whether this challenge is for `scheme`, ignoring case.
+    pub fn is(&self, scheme: &str) -> bool {
+        self.scheme[].eq_ignore_ascii_case(scheme)
+    }
+
+    /// The value of the parameter `name`, ignoring the case of the name.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.iter()
+            .find(|&&(ref n, _)| n[].eq_ignore_ascii_case(name))
+            .map(|&(_, ref value)| value[])
+    }
+
+    /// The `realm` parameter.
+    #[inline]
+    pub fn realm(&self) -> Option<&str> {
+        self.param("realm")
+    }
+}
+
+impl fmt::Show for Challenge {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        try!(self.scheme.fmt(fmt));
+        if !self.params.is_empty() {
+            try!(" ".fmt(fmt));
+            try!(fmt_auth_params(fmt, self.params[]));
+        }
+        Ok(())
+    }
+}
+
+/// Reads the challenges from every line of a raw header.
+pub fn challenges_from_raw(raw: &[Vec<u8>]) -> Option<Vec<Challenge>> {
+    let mut challenges = vec![];
+    for line in raw.iter() {
+        let parsed = match from_utf8(line[]).and_then(|s| parse_challenges(s)) {
+            Some(parsed) => parsed,
+            None => return None
+        };
+        challenges.extend(parsed.into_iter().map(|(scheme, params)| Challenge {
+            scheme: scheme,
+            params: params,
+        }));
+    }
+    if challenges.is_empty() { None } else { Some(challenges) }
+}
+
+/// Format challenges as a comma-delimited list.
+pub fn fmt_challenges(fmt: &mut fmt::Formatter, challenges: &[Challenge]) -> fmt::Result {
+    for (i, challenge) in challenges.iter().enumerate() {
+        if i > 0 {
+            try!(", ".fmt(fmt));
+        }
+        try!(challenge.fmt(fmt));
+    }
+    Ok(())
+}
+
+impl Header for WwwAuthenticate {
+    fn header_name(_: Option<WwwAuthenticate>) -> &'static str {
+        "WWW-Authenticate"
+    }
+
+    fn parse_header(raw: &[Vec<u8>]) -> Option<WwwAuthenticate> {
+        challenges_from_raw(raw).map(|c| WwwAuthenticate(c))
+    }
+}
+
+impl HeaderFormat for WwwAuthenticate {
+    fn fmt_header(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt_challenges(fmt, self.0[])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::MemReader;
+    use header::Headers;
+    use super::{WwwAuthenticate, Challenge};
+
+    fn challenge(scheme: &str, params: &[(&str, &str)]) -> Challenge {
+        Challenge {
+            scheme: scheme.into_string(),
+            params: params.iter().map(|&(n, v)| (n.into_string(), v.into_string())).collect(),
+        }
+    }
+
+    #[test]
+    fn test_parse_challenges() {
+        let mut raw = MemReader::new(b"\
+            WWW-Authenticate: Newauth realm=\"apps\", type=1, title=\"Login to \\\"apps\\\"\", \
+                              Basic realm=\"simple\"\r\n\
+            WWW-Authenticate: Negotiate abc0==, Bearer\r\n\
+            \r\n".to_vec());
+        let headers = Headers::from_raw(&mut raw).unwrap();
+        let auth = headers.get::<WwwAuthenticate>().unwrap();
+        assert_eq!(auth.0, vec![
+            challenge("Newauth", &[("realm", "apps"), ("type", "1"),
+                                   ("title", "Login to \"apps\"")]),
+            challenge("Basic", &[("realm", "simple")]),
+            challenge("Negotiate", &[]),
+            challenge("Bearer", &[]),
+        ]);
+        assert_eq!(auth.0[1].realm(), Some("simple"));
+        assert!(auth.0[1].is("basic"));
+    }
+
+    #[test]
+    fn test_format_challenges() {
+        let mut headers = Headers::new();
+        headers.set(WwwAuthenticate(vec![challenge("Basic", &[("realm", "a \"b\"")])]));
+        assert_eq!(headers.to_string()[], "WWW-Authenticate: Basic realm=\"a \\\"b\\\"\"\r\n");
+    }
+}
+
+bench_header!(bench, WwwAuthenticate, {
+    vec![b"Digest realm=\"http-auth@example.org\", qop=\"auth, auth-int\", algorithm=MD5, \
+           nonce=\"7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v\"".to_vec()]
+})
@@ -8,6 +8,10 @@ use std::str::{mod, SendStr};
 
 use url::Url;
 
+use header::Headers;
+use header::common::{Connection, TransferEncoding};
+use header::common::connection::{KeepAlive, Close};
+use header::common::transfer_encoding::Encoding::Chunked;
 use method;
 use status::StatusCode;
 use uri;
@@ -29,7 +33,11 @@ pub enum HttpReader<R> {
     /// A Reader used when a Content-Length header is passed with a positive integer.
     SizedReader(R, uint),
     /// A Reader used when Transfer-Encoding is `chunked`.
-    ChunkedReader(R, Option<uint>),
+    ///
+    /// Once the last chunk has been read, the trailer section is read as
+    /// well, and kept as `Headers` in the last field, or as the error that
+    /// reading it failed with.
+    ChunkedReader(R, Option<uint>, Option<IoResult<Headers>>),
     /// A Reader used for responses that don't indicate a length or chunked.
     ///
     /// Note: This should only used for `Response`s. It is illegal for a
@@ -57,11 +65,29 @@ impl<R: Reader> HttpReader<R> {
     pub fn unwrap(self) -> R {
         match self {
             SizedReader(r, _) => r,
-            ChunkedReader(r, _) => r,
+            ChunkedReader(r, _, _) => r,
             EofReader(r) => r,
             EmptyReader(r) => r,
         }
     }
+
+    /// Gets a borrowed reference to the underlying Reader.
+    pub fn get_ref(&self) -> &R {
+        match *self {
+            SizedReader(ref r, _) => r,
+            ChunkedReader(ref r, _, _) => r,
+            EofReader(ref r) => r,
+            EmptyReader(ref r) => r,
+        }
+    }
+
+    /// The trailers of a chunked body, once it has been read to the end.
+    pub fn trailers(&self) -> Option<&Headers> {
+        match *self {
+            ChunkedReader(_, _, Some(Ok(ref trailers))) => Some(trailers),
+            _ => None
+        }
+    }
 }
 
 impl<R: Reader> Reader for HttpReader<R> {
@@ -72,7 +98,7 @@ impl<R: Reader> Reader for HttpReader<R> {
                 if *remaining == 0 {
                     Err(io::standard_error(io::EndOfFile))
                 } else {
-                    let num = try!(body.read(buf));
+                    let num = try!(incomplete_at_eof(body.read(buf)));
                     if num > *remaining {
                         *remaining = 0;
                     } else {
@@ -81,11 +107,11 @@ impl<R: Reader> Reader for HttpReader<R> {
                     Ok(num)
                 }
             },
-            ChunkedReader(ref mut body, ref mut opt_remaining) => {
+            ChunkedReader(ref mut body, ref mut opt_remaining, ref mut trailers) => {
                 let mut rem = match *opt_remaining {
                     Some(ref rem) => *rem,
                     // None means we don't know the size of the next chunk
-                    None => try!(read_chunk_size(body))
+                    None => try!(incomplete_at_eof(read_chunk_size(body)))
                 };
                 debug!("Chunked read, remaining={}", rem);
 
@@ -95,18 +121,28 @@ impl<R: Reader> Reader for HttpReader<R> {
                     // chunk of size 0 signals the end of the chunked stream
                     // if the 0 digit was missing from the stream, it would
                     // be an InvalidInput error instead.
+                    // The trailer section after it ends with an empty line,
+                    // and has to be consumed before the next message.
+                    if trailers.is_none() {
+                        *trailers = Some(incomplete_at_eof(read_trailers(body)));
+                    }
+                    // the error is kept, since what's left of the section
+                    // can't be parsed on its own
+                    if let Some(Err(ref e)) = *trailers {
+                        return Err(e.clone());
+                    }
                     debug!("end of chunked");
                     return Err(io::standard_error(io::EndOfFile));
                 }
 
                 let to_read = min(rem, buf.len());
-                let count = try!(body.read(buf.slice_to_mut(to_read)));
+                let count = try!(incomplete_at_eof(body.read(buf.slice_to_mut(to_read))));
 
                 rem -= count;
                 *opt_remaining = if rem > 0 {
                     Some(rem)
                 } else {
-                    try!(eat(body, LINE_ENDING));
+                    try!(incomplete_at_eof(eat(body, LINE_ENDING)));
                     None
                 };
                 Ok(count)
@@ -119,6 +155,93 @@ impl<R: Reader> Reader for HttpReader<R> {
     }
 }
 
+static INCOMPLETE_MESSAGE: &'static str = "incomplete message";
+
+/// Whether `err` is the error of a message body that ended before the
+/// length it announced, or before its last chunk.
+///
+/// Reading such a body fails with this error, rather than with an
+/// `EndOfFile` that would pass for the end of the body.
+pub fn is_incomplete(err: &io::IoError) -> bool {
+    err.kind == io::OtherIoError && err.desc == INCOMPLETE_MESSAGE
+}
+
+// The stream ending within a body means the message is incomplete.
+fn incomplete_at_eof<T>(result: IoResult<T>) -> IoResult<T> {
+    match result {
+        Err(ref e) if e.kind == io::EndOfFile => Err(io::IoError {
+            kind: io::OtherIoError,
+            desc: INCOMPLETE_MESSAGE,
+            detail: Some("the connection closed within the body".into_string()),
+        }),
+        result => result
+    }
+}
+
+/// Choose how to read the body of a response to a `method` request, as
+/// described in [RFC 7230, section 3.3.3](https://tools.ietf.org/html/rfc7230#section-3.3.3).
+///
+/// Responses to `HEAD` requests, `1xx`, `204` and `304` responses, and
+/// successful responses to `CONNECT` have no body, whatever their headers
+/// say. A
+/// `Content-Length` sent along with a `Transfer-Encoding`, or with
+/// conflicting values, is an `HttpHeaderError`.
+pub fn response_reader<R: Reader>(stream: R, method: &method::Method, status: u16,
+                                  headers: &Headers) -> HttpResult<HttpReader<R>> {
+    if *method == method::Method::Head || status / 100 == 1 || status == 204 ||
+            status == 304 || (*method == method::Method::Connect && status / 100 == 2) {
+        return Ok(EmptyReader(stream));
+    }
+
+    let length = try!(content_length(headers));
+    if headers.has::<TransferEncoding>() {
+        if length.is_some() {
+            debug!("Content-Length conflicts with Transfer-Encoding");
+            return Err(HttpHeaderError);
+        }
+        return Ok(match headers.get::<TransferEncoding>() {
+            Some(&TransferEncoding(ref codings)) if codings.last() == Some(&Chunked) => {
+                ChunkedReader(stream, None, None)
+            },
+            _ => {
+                debug!("not chunked, reading until EOF");
+                EofReader(stream)
+            }
+        });
+    }
+    Ok(match length {
+        Some(len) => SizedReader(stream, len),
+        None => {
+            debug!("neither Transfer-Encoding nor Content-Length");
+            EofReader(stream)
+        }
+    })
+}
+
+// The `Content-Length`, which may be repeated, or be a list, as long as all
+// the values are the same.
+fn content_length(headers: &Headers) -> HttpResult<Option<uint>> {
+    let raw = match headers.get_raw("Content-Length") {
+        Some(raw) => raw,
+        None => return Ok(None)
+    };
+    let mut length = None;
+    for line in raw.iter() {
+        let line = match str::from_utf8(line[]) {
+            Some(line) => line,
+            None => return Err(HttpHeaderError)
+        };
+        for value in line.split(',') {
+            match (from_str::<uint>(value.trim()), length) {
+                (Some(len), None) => length = Some(len),
+                (Some(len), Some(prev)) if len == prev => (),
+                _ => return Err(HttpHeaderError)
+            }
+        }
+    }
+    Ok(length)
+}
+
 fn eat<R: Reader>(rdr: &mut R, bytes: &[u8]) -> IoResult<()> {
     for &b in bytes.iter() {
         match try!(rdr.read_byte()) {
@@ -129,6 +252,18 @@ fn eat<R: Reader>(rdr: &mut R, bytes: &[u8]) -> IoResult<()> {
     Ok(())
 }
 
+/// The trailer section of a chunked body is parsed like the headers.
+fn read_trailers<R: Reader>(rdr: &mut R) -> IoResult<Headers> {
+    match Headers::from_raw(rdr) {
+        Ok(trailers) => {
+            debug!("Trailers: [\n{}]", trailers);
+            Ok(trailers)
+        },
+        Err(HttpIoError(e)) => Err(e),
+        Err(_) => Err(io::standard_error(io::InvalidInput))
+    }
+}
+
 /// Chunked chunks start with 1*HEXDIGIT, indicating the size of the chunk.
 fn read_chunk_size<R: Reader>(rdr: &mut R) -> IoResult<uint> {
     let mut size = 0u;
@@ -190,6 +325,19 @@ impl<W: Writer> HttpWriter<W> {
         }
     }
 
+    /// Gets a mutable reference to the underlying Writer.
+    ///
+    /// Writing to it directly bypasses the framing of the body.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut W {
+        match *self {
+            ThroughWriter(ref mut w) => w,
+            ChunkedWriter(ref mut w) => w,
+            SizedWriter(ref mut w, _) => w,
+            EmptyWriter(ref mut w) => w,
+        }
+    }
+
     /// Ends the HttpWriter, and returns the underlying Writer.
     ///
     /// A final `write()` is called with an empty message, and then flushed.
@@ -251,6 +399,20 @@ impl<W: Writer> Writer for HttpWriter<W> {
     }
 }
 
+/// Whether the connection a message was received on may be kept alive
+/// after the message is complete.
+///
+/// HTTP/1.1 connections are persistent unless `Connection: close` is sent,
+/// while HTTP/1.0 connections must opt in with `Connection: keep-alive`.
+pub fn should_keep_alive(version: HttpVersion, headers: &Headers) -> bool {
+    match (version, headers.get::<Connection>()) {
+        (Http10, Some(conn)) if !conn.0.contains(&KeepAlive) => false,
+        (Http10, None) => false,
+        (Http11, Some(conn)) if conn.0.contains(&Close) => false,
+        _ => true
+    }
+}
+
 pub const SP: u8 = b' ';
 pub const CR: u8 = b'\r';
 pub const LF: u8 = b'\n';
@@ -671,12 +833,14 @@ mod tests {
     use method;
     use version::HttpVersion;
     use version::HttpVersion::{Http10, Http11, Http20};
-    use HttpError::{HttpVersionError, HttpMethodError};
+    use header::Headers;
+    use HttpError::{HttpHeaderError, HttpVersionError, HttpMethodError};
     use HttpResult;
     use url::Url;
 
     use super::{read_method, read_uri, read_http_version, read_header,
-                RawHeaderLine, read_status, RawStatus};
+                RawHeaderLine, read_status, RawStatus, response_reader, is_incomplete};
+    use super::HttpReader::{SizedReader, ChunkedReader, EofReader, EmptyReader};
 
     fn mem(s: &str) -> MemReader {
         MemReader::new(s.as_bytes().to_vec())
@@ -772,6 +936,89 @@ mod tests {
         assert_eq!(s, "foo barb");
     }
 
+    #[test]
+    fn test_read_chunked_trailers() {
+        let mut r = super::HttpReader::ChunkedReader(mem("\
+            3\r\nfoo\r\n\
+            0\r\n\
+            Grpc-Status: 0\r\n\
+            Checksum: abc\r\n\
+            \r\n\
+            next"), None, None);
+        assert_eq!(r.read_to_string(), Ok("foo".to_string()));
+        {
+            let trailers = r.trailers().unwrap();
+            assert_eq!(trailers.get_raw("grpc-status").unwrap()[0], b"0".to_vec());
+            assert_eq!(trailers.get_raw("Checksum").unwrap()[0], b"abc".to_vec());
+        }
+        // reading again doesn't read past the end of the message
+        assert_eq!(r.read(&mut [0u8, ..4]).unwrap_err().kind, io::EndOfFile);
+        assert_eq!(r.unwrap().read_to_string(), Ok("next".to_string()));
+    }
+
+    #[test]
+    fn test_read_chunked_invalid_trailers() {
+        let mut r = ChunkedReader(mem("0\r\nBad Trailer: x\r\n\r\n"), None, None);
+        assert_eq!(r.read(&mut [0u8, ..4]).unwrap_err().kind, io::InvalidInput);
+        // the rest of the section isn't taken for whole trailers
+        assert_eq!(r.read(&mut [0u8, ..4]).unwrap_err().kind, io::InvalidInput);
+        assert!(r.trailers().is_none());
+    }
+
+    #[test]
+    fn test_read_chunked_without_trailers() {
+        let mut r = super::HttpReader::ChunkedReader(mem("0\r\n\r\nnext"), None, None);
+        assert_eq!(r.read_to_string(), Ok("".to_string()));
+        assert_eq!(r.trailers().unwrap().len(), 0);
+        assert_eq!(r.unwrap().read_to_string(), Ok("next".to_string()));
+    }
+
+    #[test]
+    fn test_read_incomplete() {
+        let mut r = SizedReader(mem("foo"), 5);
+        assert!(is_incomplete(&r.read_to_end().unwrap_err()));
+        let mut r = ChunkedReader(mem("5\r\nfoo"), None, None);
+        assert!(is_incomplete(&r.read_to_end().unwrap_err()));
+        let mut r = ChunkedReader(mem("3\r\nfoo\r\n"), None, None);
+        assert!(is_incomplete(&r.read_to_end().unwrap_err()));
+    }
+
+    #[test]
+    fn test_response_reader() {
+        fn framing(method: method::Method, status: u16,
+                   headers: &[(&str, &str)]) -> HttpResult<String> {
+            let mut h = Headers::new();
+            for &(name, value) in headers.iter() {
+                h.set_raw(name.into_string(), vec![value.as_bytes().to_vec()]);
+            }
+            response_reader(mem(""), &method, status, &h).map(|reader| match reader {
+                SizedReader(_, len) => format!("sized {}", len),
+                ChunkedReader(..) => "chunked".into_string(),
+                EofReader(..) => "eof".into_string(),
+                EmptyReader(..) => "empty".into_string(),
+            })
+        }
+        let get = method::Method::Get;
+        let sized = [("Content-Length", "10")];
+        assert_eq!(framing(method::Method::Head, 200, &sized), Ok("empty".into_string()));
+        assert_eq!(framing(get.clone(), 101, &[]), Ok("empty".into_string()));
+        assert_eq!(framing(get.clone(), 204, &[]), Ok("empty".into_string()));
+        assert_eq!(framing(get.clone(), 304, &sized), Ok("empty".into_string()));
+        assert_eq!(framing(get.clone(), 200, &sized), Ok("sized 10".into_string()));
+        assert_eq!(framing(get.clone(), 200, &[("Content-Length", "10, 10")]),
+                   Ok("sized 10".into_string()));
+        assert_eq!(framing(get.clone(), 200, &[("Transfer-Encoding", "gzip, chunked")]),
+                   Ok("chunked".into_string()));
+        assert_eq!(framing(get.clone(), 200, &[("Transfer-Encoding", "chunked, gzip")]),
+                   Ok("eof".into_string()));
+        assert_eq!(framing(get.clone(), 200, &[]), Ok("eof".into_string()));
+
+        assert_eq!(framing(get.clone(), 200, &[("Content-Length", "10, 11")]),
+                   Err(HttpHeaderError));
+        let both = [("Content-Length", "10"), ("Transfer-Encoding", "chunked")];
+        assert_eq!(framing(get, 200, &both), Err(HttpHeaderError));
+    }
+
     #[bench]
     fn bench_read_method(b: &mut Bencher) {
         b.bytes = b"CONNECT ".len() as u64;
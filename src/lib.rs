@@ -105,6 +105,12 @@
 //! Client-specific functionality, such as `Request` and `Response`
 //! representations, are found in `src/client`.
 //!
+//! #### Client
+//!
+//! A `Client` creates `Request`s for you, and keeps a pool of idle
+//! keep-alive connections so that later requests to the same host don't
+//! have to connect again.
+//!
 //! #### Request
 //!
 //! An outgoing HTTP Request is represented as a struct containing a `Writer` over
@@ -134,6 +140,7 @@ extern crate openssl;
 #[cfg(test)] extern crate test;
 extern crate "unsafe-any" as uany;
 extern crate cookie;
+extern crate flate2;
 
 pub use std::io::net::ip::{SocketAddr, IpAddr, Ipv4Addr, Ipv6Addr, Port};
 pub use mimewrapper::mime;
@@ -142,14 +149,17 @@ pub use method::Method::{Get, Head, Post, Delete};
 pub use status::StatusCode::{Ok, BadRequest, NotFound};
 pub use server::Server;
 
+use std::cmp::max;
 use std::fmt;
 use std::error::{Error, FromError};
-use std::io::IoError;
+use std::io::{IoError, TimedOut};
+use std::time::Duration;
 
 use std::rt::backtrace;
 
 use self::HttpError::{HttpMethodError, HttpUriError, HttpVersionError,
-                      HttpHeaderError, HttpStatusError, HttpIoError};
+                      HttpHeaderError, HttpStatusError, HttpIoError,
+                      HttpTlsError, HttpTimeoutError};
 
 macro_rules! todo(
     ($($arg:tt)*) => (if cfg!(not(ndebug)) {
@@ -181,18 +191,44 @@ macro_rules! inspect(
     })
 )
 
+// Implements a trait for its boxed trait objects, by forwarding each of the
+// listed methods, so that a `Box<Trait + Send + Sync>` is a `Trait` too.
+macro_rules! impl_for_box(
+    ($name:ident { $(fn $method:ident(&self $(, $arg:ident: $ty:ty)*) -> $ret:ty;)* }) => (
+        impl $name for Box<$name + Send + Sync> {
+            $(
+                fn $method(&self $(, $arg: $ty)*) -> $ret {
+                    (**self).$method($($arg),*)
+                }
+            )*
+        }
+    )
+)
+
+#[cfg(test)] mod mock;
+
+// A timeout as the milliseconds that the timeouts of std's streams take,
+// shared by the streams of `net` and `unix`.
+#[inline]
+fn to_ms(timeout: Duration) -> u64 {
+    max(timeout.num_milliseconds(), 0) as u64
+}
+
 pub mod client;
+pub mod dns;
+pub mod form;
 pub mod method;
 pub mod header;
 pub mod http;
 pub mod net;
+pub mod progress;
 pub mod server;
 pub mod status;
+pub mod tls;
+pub mod unix;
 pub mod uri;
 pub mod version;
 
-#[cfg(test)] mod mock;
-
 mod mimewrapper {
     /// Re-exporting the mime crate, for convenience.
     extern crate mime;
@@ -217,6 +253,10 @@ pub enum HttpError {
     HttpStatusError,
     /// An `IoError` that occured while trying to read or write to a network stream.
     HttpIoError(IoError),
+    /// The peer of a TLS connection failed verification.
+    HttpTlsError(tls::TlsError),
+    /// A timeout expired before the operation was done.
+    HttpTimeoutError,
 }
 
 impl Error for HttpError {
@@ -228,6 +268,8 @@ impl Error for HttpError {
             HttpHeaderError => "Invalid Header provided",
             HttpStatusError => "Invalid Status provided",
             HttpIoError(_) => "An IoError occurred while connecting to the specified network",
+            HttpTlsError(_) => "The TLS peer could not be verified",
+            HttpTimeoutError => "A timeout expired",
         }
     }
 
@@ -241,7 +283,10 @@ impl Error for HttpError {
 
 impl FromError<IoError> for HttpError {
     fn from_error(err: IoError) -> HttpError {
-        HttpIoError(err)
+        match err.kind {
+            TimedOut => HttpTimeoutError,
+            _ => HttpIoError(err)
+        }
     }
 }
 
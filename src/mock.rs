@@ -1,8 +1,12 @@
 use std::fmt;
-use std::io::{IoResult, MemReader, MemWriter};
-use std::io::net::ip::{SocketAddr, ToSocketAddr};
+use std::io::{IoResult, MemReader, MemWriter, EndOfFile, TimedOut, standard_error};
+use std::io::net::ip::{SocketAddr, Port};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use net::{NetworkStream, NetworkConnector};
+use progress::Progress;
+use HttpResult;
 
 pub struct MockStream {
     pub read: MemReader,
@@ -67,8 +71,129 @@ impl NetworkStream for MockStream {
     }
 }
 
-impl NetworkConnector for MockStream {
-    fn connect<To: ToSocketAddr>(_addr: To, _scheme: &str) -> IoResult<MockStream> {
+#[deriving(Clone)]
+pub struct MockConnector;
+
+impl NetworkConnector<MockStream> for MockConnector {
+    fn connect(&self, _host: &str, _port: Port, _scheme: &str) -> HttpResult<MockStream> {
         Ok(MockStream::new())
     }
+
+    fn upgrade_tls(&self, stream: MockStream, _host: &str) -> HttpResult<MockStream> {
+        Ok(stream)
+    }
+}
+
+/// A connector whose connections answer each request with the next of the
+/// given responses, in order, starting over after the last one.
+///
+/// A response is queued every time a request is flushed, no matter which
+/// connection it was sent on, so the script works with pooled connections.
+/// Clones of a connection share what's queued on it, like the clones of a
+/// `TcpStream` would. Everything written to any of the connections is
+/// recorded. A connection with a read timeout fails with `TimedOut` when
+/// nothing is queued on it, such as after an empty response.
+#[deriving(Clone)]
+pub struct ScriptedConnector {
+    script: Arc<Mutex<Script>>,
+}
+
+struct Script {
+    responses: Vec<Vec<u8>>,
+    next: uint,
+    written: Vec<u8>,
+    connects: Vec<String>,
+}
+
+impl ScriptedConnector {
+    pub fn new(responses: &[&[u8]]) -> ScriptedConnector {
+        ScriptedConnector {
+            script: Arc::new(Mutex::new(Script {
+                responses: responses.iter().map(|res| res.to_vec()).collect(),
+                next: 0,
+                written: vec![],
+                connects: vec![],
+            }))
+        }
+    }
+
+    /// Everything written to the connections so far.
+    pub fn written(&self) -> String {
+        String::from_utf8_lossy(self.script.lock().written[]).into_string()
+    }
+
+    /// The `scheme://host:port` of every connection opened so far.
+    pub fn connects(&self) -> Vec<String> {
+        self.script.lock().connects.clone()
+    }
+}
+
+impl NetworkConnector<ScriptedStream> for ScriptedConnector {
+    fn connect(&self, host: &str, port: Port, scheme: &str) -> HttpResult<ScriptedStream> {
+        self.script.lock().connects.push(format!("{}://{}:{}", scheme, host, port));
+        Ok(ScriptedStream {
+            read: Arc::new(Mutex::new(MemReader::new(vec![]))),
+            script: self.script.clone(),
+            read_timeout: None,
+        })
+    }
+
+    fn upgrade_tls(&self, stream: ScriptedStream, _host: &str) -> HttpResult<ScriptedStream> {
+        Ok(stream)
+    }
+}
+
+#[deriving(Clone)]
+pub struct ScriptedStream {
+    read: Arc<Mutex<MemReader>>,
+    script: Arc<Mutex<Script>>,
+    read_timeout: Option<Duration>,
+}
+
+impl Reader for ScriptedStream {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        match self.read.lock().read(buf) {
+            Err(ref e) if e.kind == EndOfFile && self.read_timeout.is_some() => {
+                Err(standard_error(TimedOut))
+            },
+            result => result
+        }
+    }
+}
+
+impl Writer for ScriptedStream {
+    fn write(&mut self, msg: &[u8]) -> IoResult<()> {
+        self.script.lock().written.push_all(msg);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        let mut script = self.script.lock();
+        let res = script.responses[script.next % script.responses.len()].clone();
+        script.next += 1;
+        let mut read = self.read.lock();
+        let mut queued = read.read_to_end().unwrap_or(vec![]);
+        queued.push_all(res[]);
+        *read = MemReader::new(queued);
+        Ok(())
+    }
+}
+
+impl NetworkStream for ScriptedStream {
+    fn peer_name(&mut self) -> IoResult<SocketAddr> {
+        Ok(from_str("127.0.0.1:1337").unwrap())
+    }
+
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) {
+        self.read_timeout = timeout;
+    }
+}
+
+/// Records every call to `progress`.
+pub struct ProgressLog(pub Arc<Mutex<Vec<(u64, Option<u64>)>>>);
+
+impl Progress for ProgressLog {
+    fn progress(&self, transferred: u64, total: Option<u64>) {
+        self.0.lock().push((transferred, total));
+    }
 }
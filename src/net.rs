@@ -1,20 +1,31 @@
 //! A collection of traits abstracting over Listeners and Streams.
 use std::any::{Any, AnyRefExt};
 use std::boxed::BoxAny;
+use std::cmp::min;
 use std::fmt;
 use std::intrinsics::TypeId;
-use std::io::{IoResult, IoError, ConnectionAborted, InvalidInput, OtherIoError,
-              Stream, Listener, Acceptor};
-use std::io::net::ip::{SocketAddr, ToSocketAddr};
+use std::io::{IoResult, IoError, InvalidInput, OtherIoError, TimedOut, Stream, Listener,
+              Acceptor};
+use std::io::net::ip::{SocketAddr, ToSocketAddr, Port, Ipv4Addr, Ipv6Addr};
 use std::io::net::tcp::{TcpStream, TcpListener, TcpAcceptor};
+use std::io::timer::Timer;
 use std::mem::{mod, transmute, transmute_copy};
 use std::raw::{mod, TraitObject};
 use std::sync::{Arc, Mutex};
+use std::task::TaskBuilder;
+use std::time::Duration;
 
 use uany::UncheckedBoxAnyDowncast;
-use openssl::ssl::{SslStream, SslContext, Sslv23};
-use openssl::ssl::error::{SslError, StreamError, OpenSslErrors, SslSessionClosed};
+use openssl::ssl::SslStream;
+use time::precise_time_ns;
 
+use dns::{Resolver, CachingResolver};
+use tls::TlsConfig;
+use HttpError::HttpIoError;
+use HttpResult;
+use to_ms;
+
+use self::AddressFamily::{Ipv6, Ipv4};
 use self::HttpStream::{Http, Https};
 
 /// The write-status indicating headers have not been written.
@@ -46,15 +57,50 @@ pub trait NetworkStream: Stream + Any + Clone + Send {
     /// Get the remote address of the underlying connection.
     fn peer_name(&mut self) -> IoResult<SocketAddr>;
 
+    /// Make reads on this stream fail with a `TimedOut` error once `timeout`
+    /// has passed. `None` removes the timeout.
+    ///
+    /// The default implementation ignores timeouts.
+    #[inline]
+    fn set_read_timeout(&mut self, _timeout: Option<Duration>) {}
+
+    /// Make writes on this stream fail with a `TimedOut` error once `timeout`
+    /// has passed. `None` removes the timeout.
+    ///
+    /// The default implementation ignores timeouts.
+    #[inline]
+    fn set_write_timeout(&mut self, _timeout: Option<Duration>) {}
+
     #[doc(hidden)]
     #[inline]
     fn clone_box(&self) -> Box<NetworkStream + Send> { box self.clone() }
 }
 
 /// A connector creates a NetworkStream.
-pub trait NetworkConnector: NetworkStream {
+pub trait NetworkConnector<S: NetworkStream> {
     /// Connect to a remote address.
-    fn connect<To: ToSocketAddr>(addr: To, scheme: &str) -> IoResult<Self>;
+    fn connect(&self, host: &str, port: Port, scheme: &str) -> HttpResult<S>;
+
+    /// Connect to a remote address, giving up once `timeout` has passed.
+    ///
+    /// The default implementation ignores the timeout.
+    fn connect_timeout(&self, host: &str, port: Port, scheme: &str,
+                       _timeout: Option<Duration>) -> HttpResult<S> {
+        self.connect(host, port, scheme)
+    }
+
+    /// Start TLS over an already established connection to `host`, such as
+    /// a tunnel opened through a proxy.
+    ///
+    /// The default implementation returns an error, for connectors that
+    /// don't support TLS.
+    fn upgrade_tls(&self, _stream: S, _host: &str) -> HttpResult<S> {
+        Err(HttpIoError(IoError {
+            kind: InvalidInput,
+            desc: "TLS is not supported by this connector",
+            detail: None
+        }))
+    }
 }
 
 impl fmt::Show for Box<NetworkStream + Send> {
@@ -230,50 +276,300 @@ impl NetworkStream for HttpStream {
             Https(_, addr) => Ok(addr)
         }
     }
+
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) {
+        let ms = timeout.map(to_ms);
+        match *self {
+            Http(ref mut inner) => inner.set_read_timeout(ms),
+            Https(ref mut inner, _) => inner.lock().get_mut().set_read_timeout(ms)
+        }
+    }
+
+    fn set_write_timeout(&mut self, timeout: Option<Duration>) {
+        let ms = timeout.map(to_ms);
+        match *self {
+            Http(ref mut inner) => inner.set_write_timeout(ms),
+            Https(ref mut inner, _) => inner.lock().get_mut().set_write_timeout(ms)
+        }
+    }
 }
 
-impl NetworkConnector for HttpStream {
-    fn connect<To: ToSocketAddr>(addr: To, scheme: &str) -> IoResult<HttpStream> {
+// Connect to each of `addrs` in turn, until one succeeds. The timeout
+// covers all of the attempts.
+fn tcp_connect(addrs: &[SocketAddr], timeout: Option<Duration>) -> IoResult<TcpStream> {
+    let start = precise_time_ns();
+    let mut last_err = IoError {
+        kind: OtherIoError,
+        desc: "Host resolved to no addresses",
+        detail: None
+    };
+    for addr in addrs.iter() {
+        let result = match timeout {
+            Some(timeout) => {
+                let elapsed = Duration::nanoseconds((precise_time_ns() - start) as i64);
+                if elapsed >= timeout {
+                    break;
+                }
+                TcpStream::connect_timeout(*addr, timeout - elapsed)
+            },
+            None => TcpStream::connect(*addr)
+        };
+        match result {
+            Ok(stream) => return Ok(stream),
+            Err(e) => {
+                debug!("connecting to {} failed: {}", addr, e);
+                last_err = e;
+            }
+        }
+    }
+    Err(last_err)
+}
+
+/// The family of an IP address.
+#[deriving(Clone, PartialEq, Show)]
+pub enum AddressFamily {
+    /// An IPv6 address.
+    Ipv6,
+    /// An IPv4 address.
+    Ipv4,
+}
+
+fn family(addr: &SocketAddr) -> AddressFamily {
+    match addr.ip {
+        Ipv6Addr(..) => Ipv6,
+        Ipv4Addr(..) => Ipv4
+    }
+}
+
+// Order `addrs` to alternate between the address families, starting with
+// `preferred`, so that one broken family doesn't hold up the other.
+fn interleave(addrs: &[SocketAddr], preferred: AddressFamily) -> Vec<SocketAddr> {
+    let mut first = vec![];
+    let mut second = vec![];
+    for addr in addrs.iter() {
+        if family(addr) == preferred {
+            first.push(*addr);
+        } else {
+            second.push(*addr);
+        }
+    }
+    let mut first = first.into_iter();
+    let mut second = second.into_iter();
+    let mut addrs = vec![];
+    loop {
+        match (first.next(), second.next()) {
+            (None, None) => return addrs,
+            (a, b) => {
+                addrs.extend(a.into_iter());
+                addrs.extend(b.into_iter());
+            }
+        }
+    }
+}
+
+// How long an attempt of `race_connect` may take without a timeout, so
+// that the attempts that lost the race don't linger.
+static MAX_ATTEMPT_SECS: i64 = 30;
+
+// Race connections to `addrs`, as in RFC 8305: start each attempt `delay`
+// after the previous one, or as soon as it fails, and keep the first
+// connection established. The timeout covers all of the attempts, and
+// each of them gives up after `MAX_ATTEMPT_SECS` at most.
+fn race_connect(addrs: &[SocketAddr], delay: Duration,
+                timeout: Option<Duration>) -> IoResult<TcpStream> {
+    let start = precise_time_ns();
+    let max_attempt = Duration::seconds(MAX_ATTEMPT_SECS);
+    let mut timer = try!(Timer::new());
+    let (tx, rx) = channel();
+    let mut started = 0u;
+    let mut finished = 0u;
+    let mut last_err = IoError {
+        kind: OtherIoError,
+        desc: "Host resolved to no addresses",
+        detail: None
+    };
+    loop {
+        let remaining = match timeout {
+            Some(timeout) => {
+                let remaining = timeout - Duration::nanoseconds((precise_time_ns() - start) as i64);
+                if remaining <= Duration::zero() {
+                    return Err(IoError {
+                        kind: TimedOut,
+                        desc: "Connecting timed out",
+                        detail: None
+                    });
+                }
+                Some(remaining)
+            },
+            None => None
+        };
+        if started < addrs.len() {
+            let addr = addrs[started];
+            let attempt_timeout = remaining.map_or(max_attempt, |remaining| {
+                min(remaining, max_attempt)
+            });
+            debug!("connecting to {}", addr);
+            let attempt = tx.clone();
+            TaskBuilder::new().named("hyper connect").spawn(proc() {
+                let result = TcpStream::connect_timeout(addr, attempt_timeout);
+                // a connection that lost the race is closed as it's dropped
+                let _ = attempt.send_opt((addr, result));
+            });
+            started += 1;
+        }
+        if finished == addrs.len() {
+            return Err(last_err);
+        }
+
+        // wait for an attempt to finish, or until the next one is due or
+        // the timeout expires
+        let wait = if started < addrs.len() {
+            Some(remaining.map_or(delay, |remaining| min(remaining, delay)))
+        } else {
+            remaining
+        };
+        let attempt = match wait {
+            Some(wait) => {
+                let tick = timer.oneshot(wait);
+                select! {
+                    attempt = rx.recv() => Some(attempt),
+                    () = tick.recv() => None
+                }
+            },
+            None => Some(rx.recv())
+        };
+        // either way, the next attempt is due, unless the timeout expired
+        match attempt {
+            Some((_, Ok(stream))) => return Ok(stream),
+            Some((addr, Err(e))) => {
+                debug!("connecting to {} failed: {}", addr, e);
+                last_err = e;
+                finished += 1;
+            },
+            None => ()
+        }
+    }
+}
+
+/// A connector that will produce HttpStreams.
+///
+/// Hosts are resolved with the connector's `Resolver`, which caches the
+/// system's answers by default. When a host has several addresses, they are
+/// raced, alternating between IPv6 and IPv4: each attempt starts a short
+/// delay after the previous one, and the first connection established is
+/// kept. https connections are set up according to the connector's
+/// `TlsConfig`, which verifies the peer by default.
+#[deriving(Clone)]
+pub struct HttpConnector {
+    tls: TlsConfig,
+    resolver: Arc<Box<Resolver + Send + Sync>>,
+    attempt_delay: Option<Duration>,
+    preferred_family: AddressFamily,
+}
+
+impl HttpConnector {
+    /// Create a connector with the default `TlsConfig`.
+    pub fn new() -> HttpConnector {
+        HttpConnector::with_tls(TlsConfig::new())
+    }
+
+    /// Create a connector that sets up TLS according to `tls`.
+    pub fn with_tls(tls: TlsConfig) -> HttpConnector {
+        HttpConnector {
+            tls: tls,
+            resolver: Arc::new(box CachingResolver::new() as Box<Resolver + Send + Sync>),
+            attempt_delay: Some(Duration::milliseconds(250)),
+            preferred_family: Ipv6,
+        }
+    }
+
+    /// Get a mutable reference to the TLS configuration.
+    #[inline]
+    pub fn tls_mut(&mut self) -> &mut TlsConfig { &mut self.tls }
+
+    /// Resolve hosts with `resolver`.
+    ///
+    /// Clones of this connector made before the change keep their resolver.
+    pub fn set_resolver<R: Resolver + Send + Sync>(&mut self, resolver: R) {
+        self.resolver = Arc::new(box resolver as Box<Resolver + Send + Sync>);
+    }
+
+    /// Set how long a connection attempt to one address goes on alone,
+    /// before the next address is tried alongside it. Defaults to 250ms.
+    /// Each of the raced attempts gives up after 30 seconds at most.
+    ///
+    /// `None` tries the addresses one after the other, in the order the
+    /// resolver gave them, each only once the previous one failed.
+    pub fn set_attempt_delay(&mut self, delay: Option<Duration>) {
+        self.attempt_delay = delay;
+    }
+
+    /// Set the address family tried first. Defaults to `Ipv6`.
+    pub fn set_preferred_family(&mut self, family: AddressFamily) {
+        self.preferred_family = family;
+    }
+
+    fn tcp_connect(&self, host: &str, port: Port,
+                   timeout: Option<Duration>) -> HttpResult<TcpStream> {
+        let addrs = try!(self.resolver.resolve(host, port));
+        match self.attempt_delay {
+            Some(delay) if addrs.len() > 1 => {
+                let addrs = interleave(addrs[], self.preferred_family.clone());
+                Ok(try!(race_connect(addrs[], delay, timeout)))
+            },
+            _ => Ok(try!(tcp_connect(addrs[], timeout)))
+        }
+    }
+
+    fn wrap_ssl(&self, mut stream: TcpStream, host: &str) -> HttpResult<HttpStream> {
+        // we can't access the tcp stream once it's wrapped in an
+        // SslStream, so grab the ip address now, just in case.
+        let peer_addr = try!(stream.peer_name());
+        let stream = try!(self.tls.connect(stream, host));
+        Ok(Https(Arc::new(Mutex::new(stream)), peer_addr))
+    }
+}
+
+impl NetworkConnector<HttpStream> for HttpConnector {
+    fn connect(&self, host: &str, port: Port, scheme: &str) -> HttpResult<HttpStream> {
+        self.connect_timeout(host, port, scheme, None)
+    }
+
+    fn connect_timeout(&self, host: &str, port: Port, scheme: &str,
+                       timeout: Option<Duration>) -> HttpResult<HttpStream> {
         match scheme {
             "http" => {
                 debug!("http scheme");
-                Ok(Http(try!(TcpStream::connect(addr))))
+                Ok(Http(try!(self.tcp_connect(host, port, timeout))))
             },
             "https" => {
                 debug!("https scheme");
-                let mut stream = try!(TcpStream::connect(addr));
-                // we can't access the tcp stream once it's wrapped in an
-                // SslStream, so grab the ip address now, just in case.
-                let peer_addr = try!(stream.peer_name());
-                let context = try!(SslContext::new(Sslv23).map_err(lift_ssl_error));
-                let stream = try!(SslStream::new(&context, stream).map_err(lift_ssl_error));
-                Ok(Https(Arc::new(Mutex::new(stream)), peer_addr))
+                let mut stream = try!(self.tcp_connect(host, port, timeout));
+                // the handshake counts towards the connect timeout
+                stream.set_timeout(timeout.map(to_ms));
+                let mut stream = try!(self.wrap_ssl(stream, host));
+                stream.set_read_timeout(None);
+                stream.set_write_timeout(None);
+                Ok(stream)
             },
             _ => {
-                Err(IoError {
+                Err(HttpIoError(IoError {
                     kind: InvalidInput,
                     desc: "Invalid scheme for Http",
                     detail: None
-                })
+                }))
             }
         }
     }
-}
 
-fn lift_ssl_error(ssl: SslError) -> IoError {
-    match ssl {
-        StreamError(err) => err,
-        SslSessionClosed => IoError {
-            kind: ConnectionAborted,
-            desc: "SSL Connection Closed",
-            detail: None
-        },
-        // Unfortunately throw this away. No way to support this
-        // detail without a better Error abstraction.
-        OpenSslErrors(errs) => IoError {
-            kind: OtherIoError,
-            desc: "Error in OpenSSL",
-            detail: Some(format!("{}", errs))
+    fn upgrade_tls(&self, stream: HttpStream, host: &str) -> HttpResult<HttpStream> {
+        match stream {
+            Http(stream) => self.wrap_ssl(stream, host),
+            Https(..) => Err(HttpIoError(IoError {
+                kind: InvalidInput,
+                desc: "Stream is already using TLS",
+                detail: None
+            }))
         }
     }
 }
@@ -281,10 +577,17 @@ fn lift_ssl_error(ssl: SslError) -> IoError {
 #[cfg(test)]
 mod tests {
     use std::boxed::BoxAny;
+    use std::io::{Listener, TimedOut};
+    use std::io::net::ip::SocketAddr;
+    use std::io::net::tcp::TcpListener;
+    use std::time::Duration;
+    use time::precise_time_ns;
     use uany::UncheckedBoxAnyDowncast;
 
+    use dns::{StaticResolver, SystemResolver};
     use mock::MockStream;
-    use super::NetworkStream;
+    use super::{HttpConnector, NetworkConnector, NetworkStream, interleave, race_connect};
+    use super::AddressFamily::{Ipv6, Ipv4};
 
     #[test]
     fn test_downcast_box_stream() {
@@ -304,4 +607,67 @@ mod tests {
 
     }
 
+    #[test]
+    fn test_interleave() {
+        let addrs: Vec<SocketAddr> = ["[::1]:80", "[::2]:80", "[::3]:80", "10.0.0.1:80",
+                                      "10.0.0.2:80"]
+            .iter().map(|s| from_str(*s).unwrap()).collect();
+        let order = |addrs: Vec<SocketAddr>| -> Vec<String> {
+            addrs.iter().map(|addr| addr.to_string()).collect()
+        };
+        assert_eq!(order(interleave(addrs[], Ipv6)),
+                   vec!["[::1]:80", "10.0.0.1:80", "[::2]:80", "10.0.0.2:80", "[::3]:80"]
+                   .into_iter().map(|s| s.into_string()).collect::<Vec<String>>());
+        assert_eq!(order(interleave(addrs[], Ipv4)),
+                   vec!["10.0.0.1:80", "[::1]:80", "10.0.0.2:80", "[::2]:80", "[::3]:80"]
+                   .into_iter().map(|s| s.into_string()).collect::<Vec<String>>());
+    }
+
+    #[test]
+    fn test_race_connect_moves_on_after_failure() {
+        // nothing listens on the first address anymore
+        let refused = TcpListener::bind("127.0.0.1:0").unwrap().socket_name().unwrap();
+        let mut listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let open = listener.socket_name().unwrap();
+        let _acceptor = listener.listen().unwrap();
+
+        let start = precise_time_ns();
+        let stream = race_connect(&[refused, open], Duration::seconds(30), None);
+        assert!(stream.is_ok());
+        // the refusal started the second attempt, without waiting 30 seconds
+        assert!(precise_time_ns() - start < 10_000_000_000);
+    }
+
+    #[test]
+    fn test_connector_moves_on_after_failure() {
+        // nothing listens on the first address anymore
+        let refused = TcpListener::bind("127.0.0.1:0").unwrap().socket_name().unwrap();
+        let mut listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let open = listener.socket_name().unwrap();
+        let _acceptor = listener.listen().unwrap();
+
+        let mut resolver: StaticResolver<SystemResolver> = StaticResolver::new();
+        resolver.add("example.domain", 80, &[refused, open]);
+        let mut connector = HttpConnector::new();
+        connector.set_resolver(resolver);
+        // the addresses are tried one after the other, and then raced
+        for &delay in [None, Some(Duration::seconds(30))].iter() {
+            connector.set_attempt_delay(delay);
+            let mut stream = connector.connect("example.domain", 80, "http").unwrap();
+            assert_eq!(stream.peer_name(), Ok(open));
+        }
+    }
+
+    #[test]
+    fn test_race_connect_timed_out() {
+        // a non-routable address, so the attempt is still waiting for an
+        // answer when the deadline expires
+        let silent: SocketAddr = from_str("10.255.255.1:80").unwrap();
+
+        let start = precise_time_ns();
+        let err = race_connect(&[silent, silent], Duration::seconds(30),
+                               Some(Duration::milliseconds(100))).unwrap_err();
+        assert_eq!(err.kind, TimedOut);
+        assert!(precise_time_ns() - start < 10_000_000_000);
+    }
 }
@@ -0,0 +1,359 @@
+//! Progress and Bandwidth Throttling
+//!
+//! A `Meter` wraps a body, of a request or of a response, on the client or
+//! on the server. It reports the bytes that went through it to a
+//! `Progress` observer, and caps their rate with a `Throttle`:
+//!
+//! ```no_run
+//! # use hyper::Url;
+//! # use hyper::client::Client;
+//! # use hyper::progress::{Progress, Throttle};
+//! struct Bar;
+//!
+//! impl Progress for Bar {
+//!     fn progress(&self, transferred: u64, total: Option<u64>) {
+//!         println!("{} of {} bytes", transferred, total);
+//!     }
+//! }
+//!
+//! let client = Client::new();
+//! let url = Url::parse("http://example.domain/release.iso").unwrap();
+//! let res = client.get(url)
+//!     .download_progress(Bar)
+//!     .throttle(Throttle::new(512 * 1024))
+//!     .send();
+//! ```
+//!
+//! On the server, a `Meter` wraps a `Response<Streaming>`:
+//!
+//! ```no_run
+//! # use hyper::server::{Request, Response};
+//! # use hyper::progress::{Meter, Throttle};
+//! fn handle(_: Request, res: Response) {
+//!     let mut res = Meter::new(res.start().unwrap(), None);
+//!     res.set_throttle(Throttle::new(64 * 1024));
+//!     res.write(b"Hello World!").unwrap();
+//!     res.into_inner().end().unwrap();
+//! }
+//! ```
+//!
+//! A `Throttle` is a token bucket, which clones share, so a single one can
+//! cap the bandwidth of several bodies at once.
+use std::cmp::min;
+use std::io::IoResult;
+use std::io::timer::sleep;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use time::precise_time_ns;
+
+/// Observes the progress of a body.
+pub trait Progress: Send + Sync {
+    /// Called after each read or write, with the bytes transferred so far,
+    /// and the total expected, when it's known.
+    fn progress(&self, transferred: u64, total: Option<u64>);
+}
+
+impl_for_box!(Progress {
+    fn progress(&self, transferred: u64, total: Option<u64>) -> ();
+})
+
+impl<P: Progress> Progress for Arc<P> {
+    fn progress(&self, transferred: u64, total: Option<u64>) {
+        (**self).progress(transferred, total)
+    }
+}
+
+/// Caps the rate of bytes going through the `Meter`s that share it.
+///
+/// Clones share the same bucket.
+#[deriving(Clone)]
+pub struct Throttle {
+    bucket: Arc<Mutex<Bucket>>,
+    clock: Arc<Box<Clock + Send + Sync>>,
+}
+
+// The time, in nanoseconds, that a `Throttle` refills its bucket by.
+trait Clock: Send + Sync {
+    fn now(&self) -> u64;
+
+    fn sleep(&self, ns: u64);
+}
+
+struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        precise_time_ns()
+    }
+
+    fn sleep(&self, ns: u64) {
+        sleep(Duration::nanoseconds(ns as i64));
+    }
+}
+
+struct Bucket {
+    rate: u64,
+    capacity: u64,
+    tokens: u64,
+    refilled: u64,
+}
+
+impl Bucket {
+    fn refill(&mut self, now: u64) {
+        let added = (now - self.refilled) as f64 * self.rate as f64 / 1e9;
+        let added = added.min(self.capacity as f64) as u64;
+        if added > 0 {
+            self.put(added);
+            self.refilled = now;
+        }
+    }
+
+    fn put(&mut self, tokens: u64) {
+        self.tokens += min(tokens, self.capacity - self.tokens);
+    }
+}
+
+impl Throttle {
+    /// A throttle of `bytes_per_second`, which allows bursts of up to a
+    /// second's worth of bytes.
+    pub fn new(bytes_per_second: u64) -> Throttle {
+        Throttle::with_burst(bytes_per_second, bytes_per_second)
+    }
+
+    /// A throttle of `bytes_per_second`, which allows bursts of up to
+    /// `burst` bytes.
+    pub fn with_burst(bytes_per_second: u64, burst: u64) -> Throttle {
+        Throttle::with_clock(bytes_per_second, burst, SystemClock)
+    }
+
+    fn with_clock<C: Clock>(bytes_per_second: u64, burst: u64, clock: C) -> Throttle {
+        let capacity = if burst > 0 { burst } else { 1 };
+        Throttle {
+            bucket: Arc::new(Mutex::new(Bucket {
+                rate: if bytes_per_second > 0 { bytes_per_second } else { 1 },
+                capacity: capacity,
+                tokens: capacity,
+                refilled: clock.now(),
+            })),
+            clock: Arc::new(box clock as Box<Clock + Send + Sync>),
+        }
+    }
+
+    /// Wait until some bytes may go through, and take up to `len` of them.
+    ///
+    /// Returns how many were taken, which is 0 only if `len` is.
+    pub fn acquire(&self, len: uint) -> uint {
+        if len == 0 {
+            return 0;
+        }
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock();
+                bucket.refill(self.clock.now());
+                if bucket.tokens > 0 {
+                    let taken = min(len as u64, bucket.tokens);
+                    bucket.tokens -= taken;
+                    return taken as uint;
+                }
+                // wait for enough bytes not to wake up for every single one,
+                // but no more than a second at a time
+                let wanted = min(len as u64, bucket.capacity);
+                (wanted as f64 * 1e9 / bucket.rate as f64).min(1e9) as u64
+            };
+            self.clock.sleep(wait);
+        }
+    }
+
+    /// Give back bytes that were acquired, but didn't go through.
+    pub fn release(&self, len: uint) {
+        self.bucket.lock().put(len as u64);
+    }
+}
+
+/// A Reader or Writer that reports its progress, and may be throttled.
+pub struct Meter<S> {
+    inner: S,
+    progress: Option<Arc<Box<Progress + Send + Sync>>>,
+    throttle: Option<Throttle>,
+    transferred: u64,
+    total: Option<u64>,
+}
+
+impl<S> Meter<S> {
+    /// Wrap `inner`, which is expected to transfer `total` bytes, if that's
+    /// known.
+    pub fn new(inner: S, total: Option<u64>) -> Meter<S> {
+        Meter {
+            inner: inner,
+            progress: None,
+            throttle: None,
+            transferred: 0,
+            total: total,
+        }
+    }
+
+    /// Report the progress to `progress`.
+    pub fn set_progress<P: Progress>(&mut self, progress: P) {
+        self.progress = Some(Arc::new(box progress as Box<Progress + Send + Sync>));
+    }
+
+    /// Cap the rate of bytes with `throttle`.
+    pub fn set_throttle(&mut self, throttle: Throttle) {
+        self.throttle = Some(throttle);
+    }
+
+    /// The bytes transferred so far.
+    #[inline]
+    pub fn transferred(&self) -> u64 { self.transferred }
+
+    /// Get a reference to the wrapped Reader or Writer.
+    #[inline]
+    pub fn get_ref(&self) -> &S { &self.inner }
+
+    /// Get a mutable reference to the wrapped Reader or Writer.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut S { &mut self.inner }
+
+    /// Unwrap the Reader or Writer.
+    #[inline]
+    pub fn into_inner(self) -> S { self.inner }
+
+    fn transfer(&mut self, len: uint) {
+        self.transferred += len as u64;
+        if let Some(ref progress) = self.progress {
+            progress.progress(self.transferred, self.total);
+        }
+    }
+}
+
+impl<R: Reader> Reader for Meter<R> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        let allowed = match self.throttle {
+            Some(ref throttle) => throttle.acquire(buf.len()),
+            None => buf.len()
+        };
+        let result = self.inner.read(buf.slice_to_mut(allowed));
+        let len = *result.as_ref().unwrap_or(&0);
+        if let Some(ref throttle) = self.throttle {
+            throttle.release(allowed - len);
+        }
+        if len > 0 {
+            self.transfer(len);
+        }
+        result
+    }
+}
+
+impl<W: Writer> Writer for Meter<W> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<()> {
+        let mut written = 0;
+        while written < buf.len() {
+            let len = match self.throttle {
+                Some(ref throttle) => throttle.acquire(buf.len() - written),
+                None => buf.len() - written
+            };
+            match self.inner.write(buf[written..written + len]) {
+                Ok(()) => (),
+                Err(e) => {
+                    if let Some(ref throttle) = self.throttle {
+                        throttle.release(len);
+                    }
+                    return Err(e);
+                }
+            }
+            written += len;
+            self.transfer(len);
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufWriter, MemReader, MemWriter};
+    use std::sync::{Arc, Mutex};
+
+    use header::common::ContentLength;
+    use mock::ProgressLog;
+    use server::Response;
+    use super::{Clock, Meter, Throttle};
+
+    // A clock that only moves when it's slept on.
+    #[deriving(Clone)]
+    struct FakeClock(Arc<Mutex<u64>>);
+
+    impl Clock for FakeClock {
+        fn now(&self) -> u64 {
+            *self.0.lock()
+        }
+
+        fn sleep(&self, ns: u64) {
+            *self.0.lock() += ns;
+        }
+    }
+
+    #[test]
+    fn test_progress() {
+        let log = Arc::new(Mutex::new(vec![]));
+        let mut meter = Meter::new(MemReader::new(b"hello world".to_vec()), Some(11));
+        meter.set_progress(ProgressLog(log.clone()));
+        let mut buf = [0u8, ..6];
+        assert_eq!(meter.read(&mut buf), Ok(6));
+        assert_eq!(meter.read(&mut buf), Ok(5));
+        assert!(meter.read(&mut buf).is_err());
+        assert_eq!(*log.lock(), vec![(6, Some(11)), (11, Some(11))]);
+        assert_eq!(meter.transferred(), 11);
+    }
+
+    #[test]
+    fn test_throttle() {
+        // a burst of 100 bytes, then 1000 bytes a second
+        let clock = FakeClock(Arc::new(Mutex::new(0)));
+        let mut meter = Meter::new(MemWriter::new(), None);
+        meter.set_throttle(Throttle::with_clock(1000, 100, clock.clone()));
+        meter.write(&[0u8, ..300]).unwrap();
+        assert_eq!(clock.now(), 200_000_000);
+        assert_eq!(meter.into_inner().into_inner().len(), 300);
+    }
+
+    #[test]
+    fn test_throttle_failed_write() {
+        let clock = FakeClock(Arc::new(Mutex::new(0)));
+        let throttle = Throttle::with_clock(1000, 100, clock.clone());
+        let mut full: [u8, ..0] = [];
+        let mut meter = Meter::new(BufWriter::new(&mut full), None);
+        meter.set_throttle(throttle.clone());
+        assert!(meter.write(&[0u8, ..100]).is_err());
+        assert_eq!(meter.transferred(), 0);
+
+        // the failed write gave its bytes back
+        let mut meter = Meter::new(MemWriter::new(), None);
+        meter.set_throttle(throttle);
+        meter.write(&[0u8, ..100]).unwrap();
+        assert_eq!(clock.now(), 0);
+    }
+
+    #[test]
+    fn test_throttle_server_response() {
+        let clock = FakeClock(Arc::new(Mutex::new(0)));
+        let mut stream = MemWriter::new();
+        {
+            let mut res = Response::new(&mut stream);
+            res.headers_mut().set(ContentLength(300));
+            let mut res = Meter::new(res.start().unwrap(), Some(300));
+            res.set_throttle(Throttle::with_clock(1000, 100, clock.clone()));
+            res.write(&[b'a', ..300]).unwrap();
+            assert_eq!(res.transferred(), 300);
+            res.into_inner().end().unwrap();
+        }
+        assert_eq!(clock.now(), 200_000_000);
+        let written = stream.into_inner();
+        assert!(written.starts_with(b"HTTP/1.1 200 OK\r\n"));
+        assert!(written.ends_with(&[b'a', ..300]));
+    }
+}
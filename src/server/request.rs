@@ -5,15 +5,18 @@
 use std::io::IoResult;
 use std::io::net::ip::SocketAddr;
 
-use {HttpResult};
+use {HttpResult, form};
 use version::{HttpVersion};
 use method::Method::{mod, Get, Head};
 use header::Headers;
-use header::common::{ContentLength, TransferEncoding};
+use header::common::{ContentLength, ContentType, TransferEncoding};
 use http::{read_request_line};
 use http::HttpReader;
 use http::HttpReader::{SizedReader, ChunkedReader, EmptyReader};
+use mime::{Mime, Application, WwwFormUrlEncoded};
 use uri::RequestUri;
+use uri::RequestUri::{AbsolutePath, AbsoluteUri};
+use HttpError::HttpHeaderError;
 
 pub type InternalReader<'a> = &'a mut Reader + 'a;
 
@@ -53,7 +56,7 @@ impl<'a> Request<'a> {
             }
         } else if headers.has::<TransferEncoding>() {
             todo!("check for Transfer-Encoding: chunked");
-            ChunkedReader(stream, None)
+            ChunkedReader(stream, None, None)
         } else {
             EmptyReader(stream)
         };
@@ -67,6 +70,38 @@ impl<'a> Request<'a> {
             body: body
         })
     }
+
+    /// The trailers sent after a chunked body, once the body has been read
+    /// to the end.
+    pub fn trailers(&self) -> Option<&Headers> {
+        self.body.trailers()
+    }
+
+    /// The name/value pairs of the query of the request-uri, decoded the
+    /// same way as a form.
+    pub fn query_pairs(&self) -> Vec<(String, String)> {
+        match self.uri {
+            AbsolutePath(ref path) => match path[].find('?') {
+                Some(i) => form::parse(path[i + 1..].as_bytes()),
+                None => vec![]
+            },
+            AbsoluteUri(ref url) => form::query_pairs(url),
+            _ => vec![]
+        }
+    }
+
+    /// Read the rest of the body as an `application/x-www-form-urlencoded`
+    /// form.
+    ///
+    /// Fails with `HttpHeaderError` if the request has another `Content-Type`.
+    pub fn read_form(&mut self) -> HttpResult<Vec<(String, String)>> {
+        match self.headers.get::<ContentType>() {
+            Some(&ContentType(Mime(Application, WwwFormUrlEncoded, _))) => (),
+            _ => return Err(HttpHeaderError)
+        }
+        let body = try!(self.read_to_end());
+        Ok(form::parse(body[]))
+    }
 }
 
 impl<'a> Reader for Request<'a> {
@@ -78,6 +113,7 @@ impl<'a> Reader for Request<'a> {
 #[cfg(test)]
 mod tests {
     use mock::MockStream;
+    use HttpError::HttpHeaderError;
     use super::Request;
 
     macro_rules! sock(
@@ -122,4 +158,66 @@ mod tests {
         let mut req = Request::new(&mut stream, sock!("127.0.0.1:80")).unwrap();
         assert_eq!(req.read_to_string(), Ok("".into_string()));
     }
+
+    #[test]
+    fn test_chunked_trailers() {
+        let mut stream = MockStream::with_input(b"\
+            POST / HTTP/1.1\r\n\
+            Host: example.domain\r\n\
+            Transfer-Encoding: chunked\r\n\
+            \r\n\
+            3\r\nfoo\r\n\
+            0\r\n\
+            Grpc-Status: 0\r\n\
+            \r\n\
+        ");
+
+        let mut req = Request::new(&mut stream, sock!("127.0.0.1:80")).unwrap();
+        assert!(req.trailers().is_none());
+        assert_eq!(req.read_to_string(), Ok("foo".into_string()));
+        assert_eq!(req.trailers().unwrap().get_raw("Grpc-Status").unwrap()[0], b"0".to_vec());
+    }
+
+    #[test]
+    fn test_query_pairs() {
+        let mut stream = MockStream::with_input(b"\
+            GET /search?q=a+b&lang=en HTTP/1.1\r\n\
+            Host: example.domain\r\n\
+            \r\n\
+        ");
+
+        let req = Request::new(&mut stream, sock!("127.0.0.1:80")).unwrap();
+        assert_eq!(req.query_pairs(), vec![("q".into_string(), "a b".into_string()),
+                                           ("lang".into_string(), "en".into_string())]);
+    }
+
+    #[test]
+    fn test_read_form() {
+        let mut stream = MockStream::with_input(b"\
+            POST / HTTP/1.1\r\n\
+            Host: example.domain\r\n\
+            Content-Type: application/x-www-form-urlencoded\r\n\
+            Content-Length: 18\r\n\
+            \r\n\
+            name=J%C3%BCrgen+M\
+        ");
+
+        let mut req = Request::new(&mut stream, sock!("127.0.0.1:80")).unwrap();
+        assert_eq!(req.read_form(), Ok(vec![("name".into_string(), "J\u00fcrgen M".into_string())]));
+    }
+
+    #[test]
+    fn test_read_form_wrong_type() {
+        let mut stream = MockStream::with_input(b"\
+            POST / HTTP/1.1\r\n\
+            Host: example.domain\r\n\
+            Content-Type: text/plain\r\n\
+            Content-Length: 3\r\n\
+            \r\n\
+            a=b\
+        ");
+
+        let mut req = Request::new(&mut stream, sock!("127.0.0.1:80")).unwrap();
+        assert_eq!(req.read_form(), Err(HttpHeaderError));
+    }
 }
@@ -0,0 +1,588 @@
+//! TLS configuration for client connections.
+//!
+//! By default, the `HttpConnector` verifies the certificate chain of every
+//! https server against the system's CA certificates, and checks that the
+//! certificate was issued for the host being connected to. A `TlsConfig`
+//! can change what's trusted, present a client certificate, or pin the
+//! server's public key.
+use std::ascii::AsciiExt;
+use std::cell::RefCell;
+use std::error::FromError;
+use std::io::{IoError, MemWriter, ConnectionAborted, OtherIoError, InvalidInput};
+use std::io::fs::PathExtensions;
+use std::io::net::ip::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::io::net::tcp::TcpStream;
+use std::os;
+use std::str::from_utf8;
+
+use openssl::crypto::hash::{hash, HashType};
+use openssl::ssl::{Ssl, SslStream, SslContext, Sslv23, SslVerifyPeer, SslVerifyNone,
+                   SSL_OP_NO_SSLV2, SSL_OP_NO_SSLV3, SSL_OP_NO_TLSV1, SSL_OP_NO_TLSV1_1};
+use openssl::ssl::error::{SslError, StreamError, OpenSslErrors, SslSessionClosed};
+use openssl::x509::{X509, X509FileType, X509StoreContext};
+use serialize::base64::FromBase64;
+
+use HttpError::{HttpIoError, HttpTlsError};
+use HttpResult;
+
+use self::TlsError::{UntrustedCertificate, HostnameMismatch, PinMismatch, NoCertificate};
+use self::TlsVersion::{Tlsv1, Tlsv1_2};
+
+// Where the common distributions keep their bundle of CA certificates.
+static SYSTEM_CA_FILES: &'static [&'static str] = &[
+    "/etc/ssl/certs/ca-certificates.crt",
+    "/etc/pki/tls/certs/ca-bundle.crt",
+    "/etc/ssl/ca-bundle.pem",
+    "/etc/pki/tls/cacert.pem",
+    "/etc/ssl/cert.pem",
+    "/usr/local/share/certs/ca-root-nss.crt",
+];
+
+// Why OpenSSL rejected the certificate chain of the handshake in progress
+// on this thread, if it did.
+thread_local!(static VERIFY_ERROR: RefCell<Option<String>> = RefCell::new(None))
+
+/// A version of the TLS protocol.
+#[deriving(Clone, PartialEq, Show)]
+pub enum TlsVersion {
+    /// TLS 1.0
+    Tlsv1,
+    /// TLS 1.1
+    Tlsv1_1,
+    /// TLS 1.2
+    Tlsv1_2,
+}
+
+/// The reasons the peer of a TLS connection can fail verification.
+#[deriving(Clone, PartialEq, Show)]
+pub enum TlsError {
+    /// The certificate chain isn't signed by a trusted CA.
+    UntrustedCertificate(String),
+    /// The certificate wasn't issued for the host connected to.
+    HostnameMismatch(String),
+    /// The public key of the certificate doesn't match any pinned key.
+    PinMismatch,
+    /// The peer didn't present a certificate at all.
+    NoCertificate,
+}
+
+/// How client connections set up TLS.
+#[deriving(Clone)]
+pub struct TlsConfig {
+    verify: bool,
+    ca_files: Vec<Path>,
+    client_cert: Option<(Path, Path)>,
+    pins: Vec<Vec<u8>>,
+    min_version: TlsVersion,
+    ciphers: Option<String>,
+}
+
+impl TlsConfig {
+    /// A configuration that verifies peers against the system's CA
+    /// certificates.
+    ///
+    /// The `SSL_CERT_FILE` environment variable can point to a different
+    /// CA bundle.
+    pub fn new() -> TlsConfig {
+        TlsConfig {
+            verify: true,
+            ca_files: vec![],
+            client_cert: None,
+            pins: vec![],
+            min_version: Tlsv1,
+            ciphers: None,
+        }
+    }
+
+    /// Set whether the certificate and hostname of the peer are verified.
+    ///
+    /// Turning this off makes connections open to man-in-the-middle attacks,
+    /// and should only be done for testing.
+    pub fn set_verify(&mut self, verify: bool) {
+        self.verify = verify;
+    }
+
+    /// Trust the CA certificates in the PEM file at `path`.
+    ///
+    /// Once a CA file has been added, the system's CA certificates are no
+    /// longer trusted.
+    pub fn add_ca_file(&mut self, path: Path) {
+        self.ca_files.push(path);
+    }
+
+    /// Present the certificate and private key in the PEM files at `cert`
+    /// and `key` to servers asking for a client certificate.
+    pub fn set_client_cert(&mut self, cert: Path, key: Path) {
+        self.client_cert = Some((cert, key));
+    }
+
+    /// Pin the SHA-256 hash of a SubjectPublicKeyInfo.
+    ///
+    /// Once any pin is added, a connection is only accepted if the public
+    /// key of the peer's certificate matches one of the pins.
+    pub fn add_pin(&mut self, sha256: &[u8]) {
+        self.pins.push(sha256.to_vec());
+    }
+
+    /// Set the oldest protocol version that will be negotiated. Defaults to
+    /// TLS 1.0; SSL is never used.
+    pub fn set_min_version(&mut self, version: TlsVersion) {
+        self.min_version = version;
+    }
+
+    /// Set the ciphers that can be negotiated, as an OpenSSL cipher list
+    /// such as `HIGH:!aNULL:!MD5`.
+    pub fn set_ciphers(&mut self, ciphers: &str) {
+        self.ciphers = Some(ciphers.into_string());
+    }
+
+    /// Start TLS over `stream`, and verify that the peer is `host`.
+    pub fn connect(&self, stream: TcpStream, host: &str) -> HttpResult<SslStream<TcpStream>> {
+        let context = try!(self.context());
+        let ssl = try!(Ssl::new(&context).map_err(lift_ssl_error));
+        // SNI
+        try!(ssl.set_hostname(host).map_err(lift_ssl_error));
+
+        VERIFY_ERROR.with(|error| *error.borrow_mut() = None);
+        let stream = match SslStream::new_from(ssl, stream) {
+            Ok(stream) => stream,
+            Err(e) => {
+                if let Some(reason) = VERIFY_ERROR.with(|error| error.borrow_mut().take()) {
+                    debug!("untrusted certificate for {}: {}", host, reason);
+                    return Err(HttpTlsError(UntrustedCertificate(reason)));
+                }
+                return Err(FromError::from_error(lift_ssl_error(e)));
+            }
+        };
+
+        if self.verify || !self.pins.is_empty() {
+            let cert = match stream.get_peer_certificate() {
+                Some(cert) => cert,
+                None => return Err(HttpTlsError(NoCertificate))
+            };
+            try!(self.verify_cert(&cert, host));
+        }
+        Ok(stream)
+    }
+
+    fn context(&self) -> HttpResult<SslContext> {
+        let context = try!(SslContext::new(Sslv23).map_err(lift_ssl_error));
+
+        let mut options = SSL_OP_NO_SSLV2 | SSL_OP_NO_SSLV3;
+        if self.min_version != Tlsv1 {
+            options = options | SSL_OP_NO_TLSV1;
+        }
+        if self.min_version == Tlsv1_2 {
+            options = options | SSL_OP_NO_TLSV1_1;
+        }
+        context.set_options(options);
+
+        if self.verify {
+            context.set_verify(SslVerifyPeer, Some(record_verify_error));
+            if self.ca_files.is_empty() {
+                match system_ca_file() {
+                    Some(path) => try!(check(context.set_CA_file(&path))),
+                    None => debug!("no system CA certificates found")
+                }
+            }
+            for path in self.ca_files.iter() {
+                try!(check(context.set_CA_file(path)));
+            }
+        } else {
+            context.set_verify(SslVerifyNone, None);
+        }
+
+        if let Some((ref cert, ref key)) = self.client_cert {
+            try!(check(context.set_certificate_file(cert, X509FileType::PEM)));
+            try!(check(context.set_private_key_file(key, X509FileType::PEM)));
+        }
+        if let Some(ref ciphers) = self.ciphers {
+            try!(check(context.set_cipher_list(ciphers[])));
+        }
+        Ok(context)
+    }
+
+    fn verify_cert(&self, cert: &X509, host: &str) -> HttpResult<()> {
+        let mut pem = MemWriter::new();
+        try!(cert.write_pem(&mut pem).map_err(lift_ssl_error));
+        match pem_to_der(pem.get_ref()) {
+            Some(der) => self.verify_der(der[], host),
+            None => Err(HttpTlsError(UntrustedCertificate("malformed certificate".into_string())))
+        }
+    }
+
+    // Verify the names of a DER certificate against `host`, and its
+    // SubjectPublicKeyInfo against the pins.
+    fn verify_der(&self, der: &[u8], host: &str) -> HttpResult<()> {
+        let cert = match Certificate::parse(der) {
+            Some(cert) => cert,
+            None => {
+                let reason = "malformed certificate".into_string();
+                return Err(HttpTlsError(UntrustedCertificate(reason)));
+            }
+        };
+        if self.verify && !cert.matches(host) {
+            debug!("certificate doesn't match {}", host);
+            return Err(HttpTlsError(HostnameMismatch(host.into_string())));
+        }
+        if !self.pins.is_empty() {
+            let spki = hash(HashType::SHA256, cert.spki);
+            if !self.pins.iter().any(|pin| *pin == spki) {
+                return Err(HttpTlsError(PinMismatch));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Keep why OpenSSL rejected the chain, to tell an untrusted certificate
+// apart from other handshake failures.
+fn record_verify_error(preverify_ok: bool, ctx: &X509StoreContext) -> bool {
+    if !preverify_ok {
+        let reason = match ctx.get_error() {
+            Some(err) => format!("{}", err),
+            None => "certificate verify failed".into_string()
+        };
+        VERIFY_ERROR.with(|error| *error.borrow_mut() = Some(reason));
+    }
+    preverify_ok
+}
+
+fn pem_to_der(pem: &[u8]) -> Option<Vec<u8>> {
+    let pem = match from_utf8(pem) {
+        Some(pem) => pem,
+        None => return None
+    };
+    let base64 = pem.lines()
+        .filter(|line| !line.starts_with("-----"))
+        .collect::<Vec<&str>>()
+        .concat();
+    base64[].from_base64().ok()
+}
+
+const SEQUENCE: u8 = 0x30;
+const VERSION: u8 = 0xa0;
+const EXTENSIONS: u8 = 0xa3;
+const DNS_NAME: u8 = 0x82;
+const IP_ADDRESS: u8 = 0x87;
+// 2.5.4.3
+const COMMON_NAME: &'static [u8] = &[0x55, 0x04, 0x03];
+// 2.5.29.17
+const SUBJECT_ALT_NAME: &'static [u8] = &[0x55, 0x1d, 0x11];
+
+// A DER element.
+struct Der<'a> {
+    tag: u8,
+    contents: &'a [u8],
+    // the whole element, with its tag and length
+    raw: &'a [u8],
+}
+
+// Read the element at the start of `input`, and return it with what follows.
+fn read_der<'a>(input: &'a [u8]) -> Option<(Der<'a>, &'a [u8])> {
+    if input.len() < 2 {
+        return None;
+    }
+    let (len, start) = match input[1] {
+        len if len < 0x80 => (len as uint, 2),
+        n => {
+            let n = (n & 0x7f) as uint;
+            if n == 0 || n > 4 || input.len() < 2 + n {
+                return None;
+            }
+            (input[2..2 + n].iter().fold(0u, |len, &b| len << 8 | b as uint), 2 + n)
+        }
+    };
+    if input.len() - start < len {
+        return None;
+    }
+    Some((Der {
+        tag: input[0],
+        contents: input[start..start + len],
+        raw: input[..start + len],
+    }, input[start + len..]))
+}
+
+// The elements that make up `contents`.
+fn der_children<'a>(mut contents: &'a [u8]) -> Option<Vec<Der<'a>>> {
+    let mut children = vec![];
+    while !contents.is_empty() {
+        match read_der(contents) {
+            Some((child, rest)) => {
+                children.push(child);
+                contents = rest;
+            },
+            None => return None
+        }
+    }
+    Some(children)
+}
+
+// The contents of the single element of a DER encoding.
+fn der_inner<'a>(der: &'a [u8], tag: u8) -> Option<&'a [u8]> {
+    match read_der(der) {
+        Some((element, _)) if element.tag == tag => Some(element.contents),
+        _ => None
+    }
+}
+
+// What's verified of a certificate.
+struct Certificate<'a> {
+    common_names: Vec<&'a [u8]>,
+    dns_names: Vec<&'a [u8]>,
+    ip_addresses: Vec<&'a [u8]>,
+    spki: &'a [u8],
+}
+
+impl<'a> Certificate<'a> {
+    fn parse(der: &'a [u8]) -> Option<Certificate<'a>> {
+        let tbs = match der_inner(der, SEQUENCE).and_then(|cert| der_inner(cert, SEQUENCE)) {
+            Some(tbs) => tbs,
+            None => return None
+        };
+        let fields = match der_children(tbs) {
+            Some(fields) => fields,
+            None => return None
+        };
+        // serialNumber, signature, issuer, validity, subject, and
+        // subjectPublicKeyInfo follow the optional version
+        let first = if fields.get(0).map_or(false, |field| field.tag == VERSION) { 1 } else { 0 };
+        if fields.len() < first + 6 {
+            return None;
+        }
+
+        let mut cert = Certificate {
+            common_names: vec![],
+            dns_names: vec![],
+            ip_addresses: vec![],
+            spki: fields[first + 5].raw,
+        };
+        // a Name is a SEQUENCE of SETs of (type, value) SEQUENCEs
+        for rdn in der_children(fields[first + 4].contents).unwrap_or(vec![]).iter() {
+            for attribute in der_children(rdn.contents).unwrap_or(vec![]).iter() {
+                match der_children(attribute.contents) {
+                    Some(ref pair) if pair.len() == 2 && pair[0].contents == COMMON_NAME => {
+                        cert.common_names.push(pair[1].contents);
+                    },
+                    _ => ()
+                }
+            }
+        }
+        for field in fields[first + 6..].iter().filter(|field| field.tag == EXTENSIONS) {
+            let extensions = der_inner(field.contents, SEQUENCE).and_then(der_children);
+            for extension in extensions.unwrap_or(vec![]).iter() {
+                // extnID, an optional critical flag, and extnValue
+                let parts = der_children(extension.contents).unwrap_or(vec![]);
+                if parts.len() < 2 || parts[0].contents != SUBJECT_ALT_NAME {
+                    continue;
+                }
+                let names = der_inner(parts[parts.len() - 1].contents, SEQUENCE)
+                    .and_then(der_children);
+                for name in names.unwrap_or(vec![]).iter() {
+                    match name.tag {
+                        DNS_NAME => cert.dns_names.push(name.contents),
+                        IP_ADDRESS => cert.ip_addresses.push(name.contents),
+                        _ => ()
+                    }
+                }
+            }
+        }
+        Some(cert)
+    }
+
+    // Whether the certificate was issued for `host`. The common name is only
+    // looked at when there are no subject alternative names.
+    fn matches(&self, host: &str) -> bool {
+        let host = host.trim_left_chars('[').trim_right_chars(']');
+        if self.dns_names.is_empty() && self.ip_addresses.is_empty() {
+            return any_matches(self.common_names[], host);
+        }
+        match from_str::<IpAddr>(host) {
+            Some(ip) => {
+                let ip = ip_bytes(ip);
+                self.ip_addresses.iter().any(|addr| *addr == ip[])
+            },
+            None => any_matches(self.dns_names[], host)
+        }
+    }
+}
+
+fn any_matches(names: &[&[u8]], host: &str) -> bool {
+    names.iter().any(|name| from_utf8(*name).map_or(false, |name| matches_hostname(name, host)))
+}
+
+fn ip_bytes(ip: IpAddr) -> Vec<u8> {
+    match ip {
+        Ipv4Addr(a, b, c, d) => vec![a, b, c, d],
+        Ipv6Addr(a, b, c, d, e, f, g, h) => {
+            let mut bytes = vec![];
+            for &part in [a, b, c, d, e, f, g, h].iter() {
+                bytes.push((part >> 8) as u8);
+                bytes.push(part as u8);
+            }
+            bytes
+        }
+    }
+}
+
+fn system_ca_file() -> Option<Path> {
+    if let Some(path) = os::getenv("SSL_CERT_FILE") {
+        return Some(Path::new(path));
+    }
+    SYSTEM_CA_FILES.iter()
+        .map(|path| Path::new(*path))
+        .find(|path| path.exists())
+}
+
+/// Whether a name from a certificate matches `host`.
+///
+/// A leading `*.` in the name matches exactly one label of the host.
+pub fn matches_hostname(name: &str, host: &str) -> bool {
+    let name = name.to_ascii_lower();
+    let host = host.to_ascii_lower();
+    if name[].starts_with("*.") {
+        match host[].find('.') {
+            Some(i) if i > 0 => host[i + 1..] == name[2..],
+            _ => false
+        }
+    } else {
+        name == host
+    }
+}
+
+fn check(err: Option<SslError>) -> HttpResult<()> {
+    match err {
+        None => Ok(()),
+        Some(err) => {
+            debug!("invalid TLS configuration: {}", err);
+            Err(HttpIoError(IoError {
+                kind: InvalidInput,
+                desc: "Invalid TLS configuration",
+                detail: Some(format!("{}", err))
+            }))
+        }
+    }
+}
+
+fn lift_ssl_error(ssl: SslError) -> IoError {
+    match ssl {
+        StreamError(err) => err,
+        SslSessionClosed => IoError {
+            kind: ConnectionAborted,
+            desc: "SSL Connection Closed",
+            detail: None
+        },
+        // Unfortunately throw this away. No way to support this
+        // detail without a better Error abstraction.
+        OpenSslErrors(errs) => IoError {
+            kind: OtherIoError,
+            desc: "Error in OpenSSL",
+            detail: Some(format!("{}", errs))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serialize::hex::FromHex;
+
+    use HttpError::HttpTlsError;
+    use HttpResult;
+    use super::{TlsConfig, matches_hostname, pem_to_der};
+    use super::TlsError::{HostnameMismatch, PinMismatch};
+
+    // DNS:example.domain, DNS:*.example.domain and IP:127.0.0.1, without a
+    // common name
+    static SAN_ONLY: &'static str = "-----BEGIN CERTIFICATE-----
+MIIBrTCCAVKgAwIBAgIUTzkbmxsMo76lwSF7Z3Vn8DoqzR8wCgYIKoZIzj0EAwIw
+EDEOMAwGA1UECgwFaHlwZXIwIBcNMjYxMDE3MDA0NjQwWhgPMjEyNjA5MjMwMDQ2
+NDBaMBAxDjAMBgNVBAoMBWh5cGVyMFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAE
+/rBoTvN4c0B40FkrmbAg7Ts1HL/SFdpGNnywEojNJdYNeNgiQFY0HRRrJ8tTfrz3
+vAB3DGn/5OA0wdOe8SGZYKOBhzCBhDAdBgNVHQ4EFgQUqLMZ+gIcpQ5S3RU/3Rz1
+TNVnUOcwHwYDVR0jBBgwFoAUqLMZ+gIcpQ5S3RU/3Rz1TNVnUOcwDwYDVR0TAQH/
+BAUwAwEB/zAxBgNVHREEKjAogg5leGFtcGxlLmRvbWFpboIQKi5leGFtcGxlLmRv
+bWFpbocEfwAAATAKBggqhkjOPQQDAgNJADBGAiEA9+RitBP1M0ASbz5y4t0/hmOX
+FirWe9mmJRyhf9We4VgCIQCgIDaHgevQoHOjEe3ndluABrtc9eBa5eFDIf90p0+E
+Hw==
+-----END CERTIFICATE-----";
+
+    // CN=example.domain, but DNS:other.domain
+    static CN_SAN_MISMATCH: &'static str = "-----BEGIN CERTIFICATE-----
+MIIBojCCAUigAwIBAgIUTUG0oyiRfN2QbeOW9nMopT/GsLowCgYIKoZIzj0EAwIw
+GTEXMBUGA1UEAwwOZXhhbXBsZS5kb21haW4wIBcNMjYxMDE3MDA0NjQwWhgPMjEy
+NjA5MjMwMDQ2NDBaMBkxFzAVBgNVBAMMDmV4YW1wbGUuZG9tYWluMFkwEwYHKoZI
+zj0CAQYIKoZIzj0DAQcDQgAE/rBoTvN4c0B40FkrmbAg7Ts1HL/SFdpGNnywEojN
+JdYNeNgiQFY0HRRrJ8tTfrz3vAB3DGn/5OA0wdOe8SGZYKNsMGowHQYDVR0OBBYE
+FKizGfoCHKUOUt0VP90c9UzVZ1DnMB8GA1UdIwQYMBaAFKizGfoCHKUOUt0VP90c
+9UzVZ1DnMA8GA1UdEwEB/wQFMAMBAf8wFwYDVR0RBBAwDoIMb3RoZXIuZG9tYWlu
+MAoGCCqGSM49BAMCA0gAMEUCIQD6nh8QeNaWAUQDJOaYiGEGkGPYGDUnvujpMzjf
+b7Y1sgIgC0hmKrMeK4MgXv0sTNsUgMCJ4aeAKOXMtKrydWEJtGk=
+-----END CERTIFICATE-----";
+
+    // CN=example.domain, without subject alternative names
+    static CN_ONLY: &'static str = "-----BEGIN CERTIFICATE-----
+MIIBijCCAS+gAwIBAgIUO/dSJ3X6yDOIZPS1MLG78Yz2F6gwCgYIKoZIzj0EAwIw
+GTEXMBUGA1UEAwwOZXhhbXBsZS5kb21haW4wIBcNMjYxMDE3MDA0NjQwWhgPMjEy
+NjA5MjMwMDQ2NDBaMBkxFzAVBgNVBAMMDmV4YW1wbGUuZG9tYWluMFkwEwYHKoZI
+zj0CAQYIKoZIzj0DAQcDQgAEuOakwp1raBikuWKCpxNOCZXo/Que9b0zX55P/+CA
+yDzGcqCu0+WJRE2gWPRefVPMw1ylwygTVPPi20Lecp7eFqNTMFEwHQYDVR0OBBYE
+FAh1VgW+zbE76pZAw/vGpUoIQzJBMB8GA1UdIwQYMBaAFAh1VgW+zbE76pZAw/vG
+pUoIQzJBMA8GA1UdEwEB/wQFMAMBAf8wCgYIKoZIzj0EAwIDSQAwRgIhAMmzfXVf
+ktBRssURr53nG4O6IQkse/DJsPYPicPTcr76AiEAnTaKuoIkh0lYmzgKLtc+h7jU
+cFm1Wmb+TGC9l6YzID8=
+-----END CERTIFICATE-----";
+
+    // the SHA-256 of the SubjectPublicKeyInfo of SAN_ONLY, as in
+    // `openssl x509 -pubkey | openssl pkey -pubin -outform der | sha256sum`
+    static SAN_ONLY_PIN: &'static str =
+        "b082e9783b7c944312d080ee0d390fe5a4bc984e1eeed85b906bbcb7954d399f";
+
+    fn verify(config: &TlsConfig, pem: &str, host: &str) -> HttpResult<()> {
+        config.verify_der(pem_to_der(pem.as_bytes()).unwrap()[], host)
+    }
+
+    #[test]
+    fn test_verify_san_only() {
+        let config = TlsConfig::new();
+        assert_eq!(verify(&config, SAN_ONLY, "example.domain"), Ok(()));
+        assert_eq!(verify(&config, SAN_ONLY, "www.example.domain"), Ok(()));
+        assert_eq!(verify(&config, SAN_ONLY, "127.0.0.1"), Ok(()));
+        assert_eq!(verify(&config, SAN_ONLY, "127.0.0.2"),
+                   Err(HttpTlsError(HostnameMismatch("127.0.0.2".into_string()))));
+        assert_eq!(verify(&config, SAN_ONLY, "other.domain"),
+                   Err(HttpTlsError(HostnameMismatch("other.domain".into_string()))));
+    }
+
+    #[test]
+    fn test_verify_cn_ignored_with_san() {
+        let config = TlsConfig::new();
+        assert_eq!(verify(&config, CN_SAN_MISMATCH, "example.domain"),
+                   Err(HttpTlsError(HostnameMismatch("example.domain".into_string()))));
+        assert_eq!(verify(&config, CN_SAN_MISMATCH, "other.domain"), Ok(()));
+        // without alternative names, the common name is used
+        assert_eq!(verify(&config, CN_ONLY, "example.domain"), Ok(()));
+    }
+
+    #[test]
+    fn test_verify_pin() {
+        let mut config = TlsConfig::new();
+        config.add_pin(SAN_ONLY_PIN.from_hex().unwrap()[]);
+        assert_eq!(verify(&config, SAN_ONLY, "example.domain"), Ok(()));
+        assert_eq!(verify(&config, CN_ONLY, "example.domain"), Err(HttpTlsError(PinMismatch)));
+    }
+
+    #[test]
+    fn test_matches_hostname() {
+        assert!(matches_hostname("example.domain", "example.domain"));
+        assert!(matches_hostname("Example.Domain", "example.DOMAIN"));
+        assert!(!matches_hostname("example.domain", "other.domain"));
+    }
+
+    #[test]
+    fn test_matches_wildcard() {
+        assert!(matches_hostname("*.example.domain", "www.example.domain"));
+        assert!(!matches_hostname("*.example.domain", "example.domain"));
+        assert!(!matches_hostname("*.example.domain", "a.b.example.domain"));
+        assert!(!matches_hostname("*.example.domain", ".example.domain"));
+    }
+}
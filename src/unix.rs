@@ -0,0 +1,207 @@
+//! HTTP over Unix domain sockets.
+//!
+//! Some servers, such as Docker, only listen on a Unix domain socket. A
+//! `unix:` Url names the socket and the request path, separated by a
+//! colon:
+//!
+//! ```no_run
+//! # use hyper::Url;
+//! # use hyper::client::Client;
+//! # use hyper::unix::UnixConnector;
+//! let client = Client::with_connector(UnixConnector::new());
+//! let url = Url::parse("unix:///var/run/docker.sock:/containers/json?all=1").unwrap();
+//! let res = client.get(url).send();
+//! ```
+//!
+//! Requests over a socket are sent with a `Host` of `localhost`, since
+//! there is no host name to send.
+use std::io::{IoResult, IoError, InvalidInput};
+use std::io::net::ip::{SocketAddr, Port};
+use std::io::net::pipe::UnixStream;
+use std::time::Duration;
+
+use url::Url;
+use url::percent_encoding::lossy_utf8_percent_decode;
+
+use net::{NetworkStream, NetworkConnector};
+use HttpError::HttpIoError;
+use HttpResult;
+use to_ms;
+
+/// A connection over a Unix domain socket.
+#[deriving(Clone)]
+pub struct UnixHttpStream {
+    inner: UnixStream,
+    path: Path,
+}
+
+impl UnixHttpStream {
+    /// Connect to the socket at `path`, giving up once `timeout` has
+    /// passed.
+    pub fn connect(path: &Path, timeout: Option<Duration>) -> IoResult<UnixHttpStream> {
+        let inner = match timeout {
+            Some(timeout) => try!(UnixStream::connect_timeout(path, timeout)),
+            None => try!(UnixStream::connect(path))
+        };
+        Ok(UnixHttpStream {
+            inner: inner,
+            path: path.clone(),
+        })
+    }
+
+    /// The path of the socket.
+    #[inline]
+    pub fn path(&self) -> &Path { &self.path }
+}
+
+impl Reader for UnixHttpStream {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        self.inner.read(buf)
+    }
+}
+
+impl Writer for UnixHttpStream {
+    #[inline]
+    fn write(&mut self, msg: &[u8]) -> IoResult<()> {
+        self.inner.write(msg)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> IoResult<()> {
+        self.inner.flush()
+    }
+}
+
+impl NetworkStream for UnixHttpStream {
+    /// A Unix domain socket has no IP address, so this is always an
+    /// `InvalidInput` error naming the path of the socket instead.
+    fn peer_name(&mut self) -> IoResult<SocketAddr> {
+        Err(IoError {
+            kind: InvalidInput,
+            desc: "Unix domain sockets have no IP address",
+            detail: Some(self.path.display().to_string())
+        })
+    }
+
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) {
+        self.inner.set_read_timeout(timeout.map(to_ms));
+    }
+
+    fn set_write_timeout(&mut self, timeout: Option<Duration>) {
+        self.inner.set_write_timeout(timeout.map(to_ms));
+    }
+}
+
+/// A connector that will produce UnixHttpStreams.
+#[deriving(Clone)]
+pub struct UnixConnector {
+    socket: Option<Path>,
+}
+
+impl UnixConnector {
+    /// Connect to the socket named by each `unix:` Url.
+    pub fn new() -> UnixConnector {
+        UnixConnector { socket: None }
+    }
+
+    /// Connect to the socket at `path` for every request, whatever its Url.
+    ///
+    /// This allows plain `http:` Urls, whose host is sent as the `Host`.
+    pub fn with_socket(path: Path) -> UnixConnector {
+        UnixConnector { socket: Some(path) }
+    }
+}
+
+impl NetworkConnector<UnixHttpStream> for UnixConnector {
+    fn connect(&self, host: &str, port: Port, scheme: &str) -> HttpResult<UnixHttpStream> {
+        self.connect_timeout(host, port, scheme, None)
+    }
+
+    fn connect_timeout(&self, host: &str, _port: Port, scheme: &str,
+                       timeout: Option<Duration>) -> HttpResult<UnixHttpStream> {
+        let path = match self.socket {
+            Some(ref path) => path.clone(),
+            None if scheme == "unix" => Path::new(host),
+            None => return Err(HttpIoError(IoError {
+                kind: InvalidInput,
+                desc: "Invalid scheme for a Unix socket",
+                detail: Some(scheme.into_string())
+            }))
+        };
+        debug!("connecting to unix socket {}", path.display());
+        Ok(try!(UnixHttpStream::connect(&path, timeout)))
+    }
+}
+
+/// The path of the socket, and the request-target, of a `unix:` Url such as
+/// `unix:///var/run/docker.sock:/containers/json?all=1`.
+///
+/// Without a colon, the request-target is `/`. Paths of sockets that
+/// contain a colon can't be named this way.
+pub fn split_url(url: &Url) -> Option<(String, String)> {
+    if url.scheme[] != "unix" {
+        return None;
+    }
+    let data = match url.non_relative_scheme_data() {
+        Some(data) if data.starts_with("//") => data[2..],
+        Some(data) => data,
+        None => return None
+    };
+    let (socket, path) = match data.find(':') {
+        Some(i) => (data[..i], data[i + 1..]),
+        None => (data, "/")
+    };
+    if socket.is_empty() {
+        return None;
+    }
+
+    let mut target = String::new();
+    if !path.starts_with("/") {
+        target.push('/');
+    }
+    target.push_str(path);
+    if let Some(ref query) = url.query {
+        target.push('?');
+        target.push_str(query[]);
+    }
+    Some((lossy_utf8_percent_decode(socket.as_bytes()), target))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Listener, TempDir};
+    use std::io::net::pipe::UnixListener;
+    use url::Url;
+
+    use net::NetworkStream;
+    use super::{UnixHttpStream, split_url};
+
+    fn split(s: &str) -> Option<(String, String)> {
+        split_url(&Url::parse(s).unwrap())
+    }
+
+    #[test]
+    fn test_split_url() {
+        assert_eq!(split("unix:///var/run/docker.sock:/containers/json?all=1"),
+                   Some(("/var/run/docker.sock".into_string(),
+                         "/containers/json?all=1".into_string())));
+        assert_eq!(split("unix:/tmp/my%20app.sock"),
+                   Some(("/tmp/my app.sock".into_string(), "/".into_string())));
+        assert_eq!(split("unix:///run/app.sock:version"),
+                   Some(("/run/app.sock".into_string(), "/version".into_string())));
+        assert_eq!(split("unix://:/path"), None);
+        assert_eq!(split("http://example.domain/"), None);
+    }
+
+    #[test]
+    fn test_peer_name() {
+        // the directory is removed as it's dropped, even if the test fails
+        let dir = TempDir::new("hyper-unix-test").unwrap();
+        let path = dir.path().join("test.sock");
+        let _acceptor = UnixListener::bind(&path).unwrap().listen().unwrap();
+        let mut stream = UnixHttpStream::connect(&path, None).unwrap();
+        let err = stream.peer_name().unwrap_err();
+        assert_eq!(err.detail, Some(path.display().to_string()));
+    }
+}